@@ -0,0 +1,55 @@
+#!/usr/bin/env python3
+"""A minimal zerg_warz bot: spawns the cheapest unit whenever affordable and pushes once its army is large enough.
+
+The match writes one json message per line and expects a json array of commands on a single line after every observation.
+By default the bot talks over stdin and stdout, `--connect <port> [player]` claims a player of a match served on localhost."""
+import json
+import socket
+import sys
+
+ARMY_SIZE = 8
+
+
+def connect(port, player):
+    """Claims a player of the match served on the passed port, returning the connection's input and output"""
+    connection = socket.create_connection(("127.0.0.1", port))
+    reader, writer = connection.makefile("r"), connection.makefile("w")
+    writer.write(json.dumps({"type": "claim", "version": 1, "player": player, "name": "Example bot"}) + "\n")
+    writer.flush()
+    answer = json.loads(reader.readline())
+    if answer["type"] != "welcome":
+        sys.exit("Rejected: " + answer["reason"])
+    return reader, writer
+
+
+def play(reader, writer):
+    me = None
+    cheapest = None
+    for line in reader:
+        message = json.loads(line)
+        if message["type"] == "hello":
+            me = message["player"]
+            mobile = [unit for unit in message["roster"] if not unit["structure"]]
+            cheapest = min(mobile, key=lambda unit: unit["cost"])
+        elif message["type"] == "observation":
+            player = message["players"][me]
+            commands = []
+            if player["minerals"] >= cheapest["cost"]:
+                commands.append({"type": "spawn", "kind": cheapest["kind"]})
+            if len(player["units"]) >= ARMY_SIZE:
+                if player["order"] != "Advance":
+                    commands.append({"type": "advance"})
+            elif player["order"] == "Advance":
+                commands.append({"type": "hold", "position": 0.2})
+            writer.write(json.dumps(commands) + "\n")
+            writer.flush()
+        elif message["type"] == "end":
+            break
+
+
+if __name__ == "__main__":
+    if len(sys.argv) > 2 and sys.argv[1] == "--connect":
+        player = int(sys.argv[3]) if len(sys.argv) > 3 else None
+        play(*connect(int(sys.argv[2]), player))
+    else:
+        play(sys.stdin, sys.stdout)
@@ -0,0 +1,121 @@
+use status::StatusEffect;
+
+
+/// An active ability of a `GameChar`, as defined in the data file
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Ability{
+    pub name: String,
+    pub effect: AbilityEffect,
+    /// Energy it costs to use the ability
+    #[serde(default)]
+    pub energy: f32,
+    /// Hp it costs to use the ability
+    #[serde(default)]
+    pub hp: f32,
+    /// Time in seconds before the ability can be used again
+    #[serde(default)]
+    pub cooldown: f32,
+    /// Time in seconds the effect lasts.
+    /// Abilities without a duration are toggled on and off with each use
+    #[serde(default)]
+    pub duration: f32,
+}
+
+/// What an `Ability` does while it is in effect
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "lowercase")]
+pub enum AbilityEffect{
+    /// The unit can neither move, attack nor be attacked, and regenerates
+    /// the specified amount of hp per second
+    Burrow{
+        regeneration: f32,
+    },
+    /// The unit's speed and damage are multiplied by the specified factors
+    Boost{
+        #[serde(default = "one")]
+        speed: f32,
+        #[serde(default = "one")]
+        damage: f32,
+    },
+    /// Every attack of the unit inflicts the specified status effect on its target
+    Inflict{
+        status: StatusEffect,
+    },
+    /// The unit can't move, but its range is increased by `range`
+    /// and its damage multiplied by `damage`
+    Deploy{
+        #[serde(default)]
+        range: f32,
+        #[serde(default = "one")]
+        damage: f32,
+    },
+}
+
+fn one() -> f32 {
+    1.0
+}
+
+
+/// An `Ability` of a single `GameChar`, tracking its cooldown and whether it is in effect
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AbilityState{
+    pub ability: Ability,
+    /// Time in seconds until the ability can be used again
+    pub cooldown: f32,
+    /// Time in seconds the effect still lasts
+    pub active: f32,
+    /// Whether a toggled ability is switched on
+    pub toggled: bool,
+}
+
+impl AbilityState {
+    /// Returns a new `AbilityState` of the passed ability, ready to be used
+    pub fn new(ability: Ability) -> AbilityState {
+        AbilityState{
+            ability,
+            cooldown: 0.0,
+            active: 0.0,
+            toggled: false,
+        }
+    }
+
+    /// Returns true if the ability is currently in effect
+    pub fn is_active(&self) -> bool {
+        self.toggled || self.active > 0.0
+    }
+
+    /// Returns true if the ability is toggled on and off instead of lasting for a duration
+    pub fn is_toggle(&self) -> bool {
+        self.ability.duration == 0.0
+    }
+
+    /// Returns true if the ability is off cooldown and the passed energy suffices to use it.
+    /// Toggled abilities can always be turned off once their cooldown is over
+    pub fn is_ready(&self, energy: f32) -> bool {
+        let toggling_off = self.is_toggle() && self.is_active();
+        self.cooldown <= 0.0 && (toggling_off || energy >= self.ability.energy)
+    }
+
+    /// Uses the ability, returning the energy that has been spent.
+    /// Assumes `is_ready()` has been checked beforehand
+    pub fn activate(&mut self) -> f32 {
+        self.cooldown = self.ability.cooldown;
+        if self.is_toggle() {
+            //toggled abilities stay in effect until they are used again
+            if self.toggled {
+                self.toggled = false;
+                return 0.0;
+            }
+            self.toggled = true;
+        } else {
+            self.active = self.ability.duration;
+        }
+        self.ability.energy
+    }
+
+    /// Advances cooldown and effect duration by `delta` seconds
+    pub fn update(&mut self, delta: f32){
+        self.cooldown = (self.cooldown - delta).max(0.0);
+        self.active = (self.active - delta).max(0.0);
+    }
+}
@@ -0,0 +1,337 @@
+use ggez::{GameResult, GameError};
+use serde_json;
+
+use std::fs::File;
+use std::io::Write;
+
+use gameobject::{GameChar, UnitKind, UnitDef};
+use data::GameData;
+use dice::Dice;
+use setup::LaneEnd;
+use gamestate::Side;
+use constants::TICKS_PER_SECOND;
+
+
+/// Both armies start this far away from their own base, so they meet shortly after the start of a fight
+const FIGHT_START: f32 = 0.4;
+
+/// Fights that haven't been decided after this many seconds count as a draw
+const FIGHT_TIMEOUT: f32 = 120.0;
+
+/// A mix of unit kinds, scaled up to the mineral budget of a matchup matrix
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Composition{
+    pub name: String,
+    /// Kinds of units with their share of the army, e.g. `[["ling", 2], ["hydra", 1]]`
+    pub units: Vec<(UnitKind, u32)>,
+}
+
+impl Composition {
+    /// Returns a composition consisting of a single kind of unit
+    pub fn single(def: &UnitDef) -> Composition {
+        Composition{
+            name: def.name.clone(),
+            units: vec!((def.kind, 1)),
+        }
+    }
+
+    /// Reads a list of compositions from the json file at the passed path
+    pub fn read(path: &str) -> GameResult<Vec<Composition>> {
+        let file = File::open(path)?;
+        serde_json::from_reader(file)
+            .map_err(|e| GameError::ResourceLoadError(format!("invalid compositions: {}", e)))
+    }
+
+    /// Returns the units of the composition, as many times over as `budget` minerals allow but at least once,
+    /// with what is left of the budget spent on more of its cheapest unit
+    fn army<'a>(&self, data: &'a GameData, budget: u32) -> GameResult<Vec<(&'a UnitDef, u32)>> {
+        let mut army = Vec::with_capacity(self.units.len());
+        for &(kind, count) in &self.units {
+            army.push((unit_def(data, kind)?, count));
+        }
+        let cost: u32 = army.iter().map(|&(def, count)| def.stats.cost*count).sum();
+        let times = (budget/cost.max(1)).max(1);
+        for unit in &mut army {
+            unit.1 *= times;
+        }
+        let left = budget.saturating_sub(cost*times);
+        if let Some(cheapest) = army.iter_mut().min_by_key(|unit| unit.0.stats.cost) {
+            cheapest.1 += left/cheapest.0.stats.cost.max(1);
+        }
+        Ok(army)
+    }
+
+    /// Returns the cost of the cheapest unit of the composition
+    fn cheapest(&self, data: &GameData) -> GameResult<u32> {
+        let mut cheapest = None;
+        for &(kind, _) in &self.units {
+            let cost = unit_def(data, kind)?.stats.cost;
+            cheapest = Some(cheapest.map_or(cost, |cheapest: u32| cheapest.min(cost)));
+        }
+        cheapest.ok_or_else(|| GameError::UnknownError(format!("{} has no units", self.name)))
+    }
+}
+
+/// Returns the smallest budget of at least `minimum` minerals that the cheapest unit of every composition divides,
+/// so the armies of all compositions can be filled up to the same mineral value
+pub fn fair_budget(data: &GameData, compositions: &[Composition], minimum: u32) -> GameResult<u32> {
+    let mut multiple = 1;
+    for composition in compositions {
+        let cost = composition.cheapest(data)?.max(1);
+        multiple = multiple/gcd(multiple, cost)*cost;
+    }
+    Ok(minimum.max(1).div_ceil(multiple)*multiple)
+}
+
+fn gcd(a: u32, b: u32) -> u32 {
+    if b == 0 {a} else {gcd(b, a % b)}
+}
+
+/// The outcome of all fights of one composition against another
+#[derive(Debug, Clone, Default)]
+pub struct Matchup{
+    pub fights: u32,
+    pub wins: u32,
+    pub draws: u32,
+    /// Total seconds until the losing army was wiped out, over all decided fights
+    seconds: f32,
+    /// Total mineral value of the surviving units of the first composition, weighted by their remaining hp
+    remaining: f32,
+}
+
+impl Matchup {
+    /// Share of fights the first composition won, draws counting as half a win
+    pub fn win_rate(&self) -> f32 {
+        (self.wins as f32 + self.draws as f32/2.0)/self.fights.max(1) as f32
+    }
+
+    /// Average seconds until the losing army was wiped out, `None` if every fight was a draw
+    pub fn time_to_kill(&self) -> Option<f32> {
+        let decided = self.fights - self.draws;
+        if decided == 0 {
+            None
+        } else {
+            Some(self.seconds/decided as f32)
+        }
+    }
+
+    /// Average mineral value of the first composition left standing after a fight
+    pub fn remaining_value(&self) -> f32 {
+        self.remaining/self.fights.max(1) as f32
+    }
+}
+
+/// Win rates, time-to-kill and remaining value of every composition against every other,
+/// fought at equal mineral cost
+#[derive(Debug, Clone)]
+pub struct Matrix{
+    pub compositions: Vec<Composition>,
+    pub budget: u32,
+    /// Mineral value of the army of each composition, which may miss the budget if its units don't add up to it
+    pub values: Vec<u32>,
+    /// `matchups[i][j]` holds the fights of composition i against composition j
+    pub matchups: Vec<Vec<Matchup>>,
+}
+
+impl Matrix {
+    /// Fights every composition against every other `fights` times, each army worth `budget` minerals.
+    /// The compositions swap sides every fight, and fight `n` of each matchup rolls its dice from `seed + n`
+    pub fn run(data: &GameData, compositions: Vec<Composition>, budget: u32, fights: u32, seed: u64) -> GameResult<Matrix> {
+        let mut values = Vec::with_capacity(compositions.len());
+        for composition in &compositions {
+            values.push(composition.army(data, budget)?.iter().map(|&(def, count)| def.stats.cost*count).sum());
+        }
+        let mut matchups = Vec::with_capacity(compositions.len());
+        for first in &compositions {
+            let mut row = Vec::with_capacity(compositions.len());
+            for second in &compositions {
+                let mut matchup = Matchup::default();
+                for n in 0..fights {
+                    let mut dice = Dice::new(seed.wrapping_add(n as u64), data.randomness);
+                    let swapped = n % 2 == 1;
+                    let (left, right) = if swapped {(second, first)} else {(first, second)};
+                    let mut armies = (
+                        spawn(&left.army(data, budget)?, Side::Left),
+                        spawn(&right.army(data, budget)?, Side::Right),
+                    );
+                    let ticks = fight(&mut armies, &mut dice);
+                    let (first_army, second_army) = if swapped {(&armies.1, &armies.0)} else {(&armies.0, &armies.1)};
+                    let (first_alive, second_alive) = (!first_army.is_empty(), !second_army.is_empty());
+                    matchup.fights += 1;
+                    if first_alive == second_alive {
+                        matchup.draws += 1;
+                    } else {
+                        if first_alive {
+                            matchup.wins += 1;
+                        }
+                        matchup.seconds += ticks as f32/TICKS_PER_SECOND as f32;
+                    }
+                    matchup.remaining += value(first_army);
+                }
+                row.push(matchup);
+            }
+            matchups.push(row);
+        }
+        Ok(Matrix{
+            compositions: compositions,
+            budget: budget,
+            values: values,
+            matchups: matchups,
+        })
+    }
+
+    /// Returns one line per matchup, with a header
+    pub fn to_csv(&self) -> String {
+        let mut csv = String::from("composition,opponent,value,opponent_value,fights,wins,draws,win_rate,time_to_kill,remaining_value\n");
+        for (i, row) in self.matchups.iter().enumerate() {
+            for (j, matchup) in row.iter().enumerate() {
+                csv.push_str(&format!("{},{},{},{},{},{},{},{:.3},{},{:.0}\n",
+                    self.compositions[i].name, self.compositions[j].name, self.values[i], self.values[j],
+                    matchup.fights, matchup.wins, matchup.draws, matchup.win_rate(),
+                    matchup.time_to_kill().map(|seconds| format!("{:.1}", seconds)).unwrap_or_default(),
+                    matchup.remaining_value()));
+            }
+        }
+        csv
+    }
+
+    /// Returns a table with a row per composition and a column per opponent, each named with the value of its army.
+    /// Each cell holds the win rate, time-to-kill and remaining value of the row's composition
+    pub fn to_markdown(&self) -> String {
+        let mut table = format!("Win rate / time-to-kill / remaining value of the row against the column, at {} minerals\n\n", self.budget);
+        table.push_str("| |");
+        for (composition, value) in self.compositions.iter().zip(&self.values) {
+            table.push_str(&format!(" {} ({}) |", composition.name, value));
+        }
+        table.push_str("\n| --- |");
+        for _ in &self.compositions {
+            table.push_str(" --- |");
+        }
+        table.push('\n');
+        for (i, row) in self.matchups.iter().enumerate() {
+            table.push_str(&format!("| **{}** ({}) |", self.compositions[i].name, self.values[i]));
+            for matchup in row {
+                let seconds = matchup.time_to_kill().map(|seconds| format!("{:.1}s", seconds)).unwrap_or_else(|| String::from("-"));
+                table.push_str(&format!(" {:.0}% / {} / {:.0} |", matchup.win_rate()*100.0, seconds, matchup.remaining_value()));
+            }
+            table.push('\n');
+        }
+        table
+    }
+
+    /// Writes the matrix to the passed paths, as CSV and Markdown
+    pub fn write(&self, csv: &str, markdown: &str) -> GameResult<()> {
+        File::create(csv)?.write_all(self.to_csv().as_bytes())?;
+        File::create(markdown)?.write_all(self.to_markdown().as_bytes())?;
+        Ok(())
+    }
+}
+
+/// Returns a composition for every mobile unit kind of every faction
+pub fn unit_compositions(data: &GameData) -> Vec<Composition> {
+    data.factions.iter()
+        .flat_map(|faction| &faction.units)
+        .filter(|def| !def.structure)
+        .map(Composition::single)
+        .collect()
+}
+
+/// Returns the definition of the passed kind of unit, from whichever faction has it
+fn unit_def(data: &GameData, kind: UnitKind) -> GameResult<&UnitDef> {
+    data.factions.iter()
+        .flat_map(|faction| &faction.units)
+        .find(|def| def.kind == kind)
+        .ok_or_else(|| GameError::UnknownError(format!("No faction has {:?} units", kind)))
+}
+
+/// Returns the units of an army on the passed side of a single lane, close to the middle
+fn spawn(army: &[(&UnitDef, u32)], side: Side) -> Vec<GameChar> {
+    let mut units = Vec::new();
+    for &(def, count) in army {
+        for _ in 0..count {
+            let mut unit = GameChar::new(def, LaneEnd{lane: 0, side: side});
+            if !def.structure {
+                unit.nudge(FIGHT_START);
+            }
+            units.push(unit);
+        }
+    }
+    units
+}
+
+/// Lets the armies on the left and right attack-move towards each other until one of them, or both,
+/// are wiped out or the fight times out. Like in a match, the left army attacks first each tick.
+/// Returns the number of ticks the fight lasted
+fn fight(armies: &mut (Vec<GameChar>, Vec<GameChar>), dice: &mut Dice) -> u64 {
+    let delta = 1.0/TICKS_PER_SECOND as f32;
+    let mut ticks = 0;
+    while !armies.0.is_empty() && !armies.1.is_empty() && ticks as f32*delta < FIGHT_TIMEOUT {
+        ticks += 1;
+        {
+            let (ref mut left, ref mut right) = *armies;
+            {
+                let mut enemies: Vec<&mut GameChar> = right.iter_mut().collect();
+                for unit in left.iter_mut() {
+                    unit.attack_move(&mut enemies, &mut [], &mut [], dice);
+                }
+            }
+            let mut enemies: Vec<&mut GameChar> = left.iter_mut().collect();
+            for unit in right.iter_mut() {
+                unit.attack_move(&mut enemies, &mut [], &mut [], dice);
+            }
+        }
+        for army in [&mut armies.0, &mut armies.1].iter_mut() {
+            for unit in army.iter_mut() {
+                unit.update_abilities(delta);
+                unit.update_statuses(delta);
+            }
+            army.retain(|unit| unit.stats.hp > 0.0);
+        }
+    }
+    ticks
+}
+
+/// Returns the mineral value of the passed units, weighted by their remaining hp
+fn value(units: &[GameChar]) -> f32 {
+    units.iter().map(|unit| unit.stats.cost as f32*unit.stats.hp/unit.max_hp).sum()
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn cost(army: &[(&UnitDef, u32)]) -> u32 {
+        army.iter().map(|&(def, count)| def.stats.cost*count).sum()
+    }
+
+    #[test]
+    fn armies_are_filled_up_with_the_cheapest_unit() {
+        let data = GameData::bundled();
+        let composition = Composition{name: String::from("Tank/Marine"), units: vec!((UnitKind::Tank, 1), (UnitKind::Marine, 4))};
+        let army = composition.army(&data, 1500).unwrap();
+        assert_eq!(cost(&army), 1500);
+        assert_eq!(army.iter().map(|&(def, count)| (def.kind, count)).collect::<Vec<_>>(),
+            vec!((UnitKind::Tank, 1), (UnitKind::Marine, 7)));
+    }
+
+    #[test]
+    fn armies_never_exceed_the_budget() {
+        let data = GameData::bundled();
+        for composition in unit_compositions(&data) {
+            assert!(cost(&composition.army(&data, 1000).unwrap()) <= 1000, "{} is over budget", composition.name);
+        }
+    }
+
+    #[test]
+    fn fair_budget_gives_every_army_the_same_value() {
+        let data = GameData::bundled();
+        let mut compositions = unit_compositions(&data);
+        compositions.extend(Composition::read(concat!(env!("CARGO_MANIFEST_DIR"), "/compositions.json")).unwrap());
+        let budget = fair_budget(&data, &compositions, 1000).unwrap();
+        assert!(budget >= 1000);
+        for composition in &compositions {
+            assert_eq!(cost(&composition.army(&data, budget).unwrap()), budget, "{} misses the budget", composition.name);
+        }
+    }
+}
@@ -0,0 +1,381 @@
+use ggez::{GameResult, GameError};
+use serde_json;
+
+use std::fs::File;
+
+use player::{Player, Command};
+use gameobject::{UnitKind, Order};
+
+
+/// An AI whose decisions follow a behavior tree, loaded from a json file like those in `trees/`.
+/// The tree is evaluated from its root on every decision, every action it reaches issues its command
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct BehaviorTree{
+    pub root: Node,
+}
+
+/// A node of a `BehaviorTree`. Evaluating a node either succeeds or fails
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum Node{
+    /// Evaluates its children in order until one fails, succeeding if none does
+    Sequence{
+        #[serde(default)]
+        name: Option<String>,
+        children: Vec<Node>,
+    },
+    /// Evaluates its children in order until one succeeds, failing if none does
+    Selector{
+        #[serde(default)]
+        name: Option<String>,
+        children: Vec<Node>,
+    },
+    /// Succeeds if its child fails and the other way around
+    Invert{
+        #[serde(default)]
+        name: Option<String>,
+        child: Box<Node>,
+    },
+    /// Evaluates its child and succeeds either way
+    Succeed{
+        #[serde(default)]
+        name: Option<String>,
+        child: Box<Node>,
+    },
+    /// Succeeds if the condition holds
+    Condition{
+        #[serde(default)]
+        name: Option<String>,
+        condition: Condition,
+    },
+    /// Issues a command, failing if the player can't carry it out
+    Action{
+        #[serde(default)]
+        name: Option<String>,
+        action: Action,
+    },
+}
+
+/// Something a `Node::Condition` checks about the match
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Condition{
+    /// The player has at least this many minerals left to spend on this decision
+    Minerals(u32),
+    /// The opponents together have more than this many times the player's units
+    Outnumbered(f32),
+    /// The player has fewer units of the kind than the opponents have of the kinds it counters
+    Countered(UnitKind),
+    /// The player has fewer than `than` units of `kind`
+    Fewer{
+        kind: UnitKind,
+        than: u32,
+    },
+    /// The opponents together have at least `at_least` units of `kind`
+    Enemy{
+        kind: UnitKind,
+        at_least: u32,
+    },
+    /// An opposing army has advanced beyond this position, measured like `GameChar::advance()`
+    EnemyFront(f32),
+    /// The player's base has less hp left than this
+    BaseHp(f32),
+    /// The army's current order
+    Advancing,
+    Holding,
+    Retreating,
+}
+
+/// A command a `Node::Action` issues
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Action{
+    /// Spawns a unit or structure of the kind
+    Spawn(UnitKind),
+    /// Morphs the rearmost unit that can turn into the kind
+    Morph(UnitKind),
+    /// Researches the next level of the upgrade with this name
+    Research(String),
+    /// Orders the army to hold at this position, measured like `GameChar::advance()`
+    Hold(f32),
+    Advance,
+    Retreat,
+}
+
+/// A node that was evaluated on a decision, with its depth in the tree and whether it succeeded
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Trace{
+    pub depth: usize,
+    pub label: String,
+    pub success: bool,
+}
+
+/// What the tree knows while evaluating a decision
+struct Blackboard<'a>{
+    player: &'a Player,
+    opponents: &'a [&'a Player],
+    /// Minerals not yet spent on the commands of this decision
+    minerals: u32,
+    /// The army's order, as it will be once the commands of this decision are carried out
+    order: Order,
+    commands: Vec<Command>,
+}
+
+impl BehaviorTree {
+    /// Reads the tree from the json file at the passed path
+    pub fn read(path: &str) -> GameResult<BehaviorTree> {
+        let file = File::open(path)?;
+        serde_json::from_reader(file)
+            .map_err(|e| GameError::ResourceLoadError(format!("invalid behavior tree: {}", e)))
+    }
+
+    /// Returns the commands the tree decides on for the passed player
+    pub fn decide(&self, player: &Player, opponents: &[&Player]) -> Vec<Command> {
+        let mut blackboard = Blackboard::new(player, opponents);
+        self.root.evaluate(&mut blackboard, 0, &mut None);
+        blackboard.commands
+    }
+
+    /// Returns the commands the tree decides on for the passed player together with every node it evaluated, in order,
+    /// to see why it decided what it did
+    pub fn decide_traced(&self, player: &Player, opponents: &[&Player]) -> (Vec<Command>, Vec<Trace>) {
+        let mut blackboard = Blackboard::new(player, opponents);
+        let mut trace = Some(Vec::new());
+        self.root.evaluate(&mut blackboard, 0, &mut trace);
+        (blackboard.commands, trace.unwrap_or_default())
+    }
+}
+
+impl<'a> Blackboard<'a> {
+    fn new(player: &'a Player, opponents: &'a [&'a Player]) -> Blackboard<'a> {
+        Blackboard{
+            player: player,
+            opponents: opponents,
+            minerals: player.minerals,
+            order: player.order,
+            commands: Vec::new(),
+        }
+    }
+
+    fn enemy_count(&self, kind: UnitKind) -> u32 {
+        self.opponents.iter().map(|opponent| opponent.count(kind)).sum()
+    }
+
+    /// Gives the army an order, unless it already has it
+    fn order(&mut self, order: Order) -> bool {
+        if self.order != order {
+            self.commands.push(match order {
+                Order::Advance => Command::Advance,
+                Order::Hold(position) => Command::Hold(position),
+                Order::Retreat => Command::Retreat,
+            });
+            self.order = order;
+        }
+        true
+    }
+}
+
+impl Node {
+    /// Returns a short description of the node, its name if it has one
+    pub fn label(&self) -> String {
+        let (name, label) = match *self {
+            Node::Sequence{ref name, ..} => (name, String::from("Sequence")),
+            Node::Selector{ref name, ..} => (name, String::from("Selector")),
+            Node::Invert{ref name, ..} => (name, String::from("Invert")),
+            Node::Succeed{ref name, ..} => (name, String::from("Succeed")),
+            Node::Condition{ref name, ref condition} => (name, format!("{:?}", condition)),
+            Node::Action{ref name, ref action} => (name, format!("{:?}", action)),
+        };
+        name.clone().unwrap_or(label)
+    }
+
+    /// Evaluates the node and returns whether it succeeded, recording it in `trace` if there is one
+    fn evaluate(&self, blackboard: &mut Blackboard, depth: usize, trace: &mut Option<Vec<Trace>>) -> bool {
+        let index = trace.as_mut().map(|trace| {
+            trace.push(Trace{depth: depth, label: self.label(), success: false});
+            trace.len() - 1
+        });
+        let success = match *self {
+            Node::Sequence{ref children, ..} => children.iter().all(|child| child.evaluate(blackboard, depth + 1, trace)),
+            Node::Selector{ref children, ..} => children.iter().any(|child| child.evaluate(blackboard, depth + 1, trace)),
+            Node::Invert{ref child, ..} => !child.evaluate(blackboard, depth + 1, trace),
+            Node::Succeed{ref child, ..} => {
+                child.evaluate(blackboard, depth + 1, trace);
+                true
+            }
+            Node::Condition{ref condition, ..} => condition.check(blackboard),
+            Node::Action{ref action, ..} => action.perform(blackboard),
+        };
+        if let (Some(trace), Some(index)) = (trace.as_mut(), index) {
+            trace[index].success = success;
+        }
+        success
+    }
+}
+
+impl Condition {
+    fn check(&self, blackboard: &Blackboard) -> bool {
+        let player = blackboard.player;
+        match *self {
+            Condition::Minerals(minerals) => blackboard.minerals >= minerals,
+            Condition::Outnumbered(ratio) => {
+                let enemies: usize = blackboard.opponents.iter().map(|opponent| opponent.units.len()).sum();
+                enemies as f32 > player.units.len() as f32*ratio
+            }
+            Condition::Countered(kind) => match player.unit_def(kind) {
+                Ok(def) => player.count(kind) < def.counters.iter().map(|&countered| blackboard.enemy_count(countered)).sum(),
+                Err(_) => false,
+            },
+            Condition::Fewer{kind, than} => player.count(kind) < than,
+            Condition::Enemy{kind, at_least} => blackboard.enemy_count(kind) >= at_least,
+            Condition::EnemyFront(position) => blackboard.opponents.iter().any(|opponent| opponent.front() > position),
+            Condition::BaseHp(hp) => player.base.hp < hp,
+            Condition::Advancing => blackboard.order == Order::Advance,
+            Condition::Holding => match blackboard.order {
+                Order::Hold(_) => true,
+                _ => false,
+            },
+            Condition::Retreating => blackboard.order == Order::Retreat,
+        }
+    }
+}
+
+impl Action {
+    fn perform(&self, blackboard: &mut Blackboard) -> bool {
+        let player = blackboard.player;
+        match *self {
+            Action::Spawn(kind) => {
+                let def = match player.unit_def(kind) {
+                    Ok(def) => def,
+                    Err(_) => return false,
+                };
+                let spawned = blackboard.commands.iter().filter(|&&command| command == Command::Spawn(kind)).count() as u32;
+                let limited = def.limit.map(|limit| player.count(kind) + spawned >= limit).unwrap_or(false);
+                if limited || def.stats.cost > blackboard.minerals {
+                    return false;
+                }
+                blackboard.minerals -= def.stats.cost;
+                blackboard.commands.push(Command::Spawn(kind));
+                true
+            }
+            Action::Morph(into) => {
+                let index = player.morphs.iter().position(|morph| {
+                    morph.into == into && morph.cost <= blackboard.minerals
+                        && player.units.iter().any(|unit| unit.kind == morph.from && unit.morphing.is_none())
+                });
+                match index {
+                    Some(index) => {
+                        blackboard.minerals -= player.morphs[index].cost;
+                        blackboard.commands.push(Command::Morph(index));
+                        true
+                    }
+                    None => false,
+                }
+            }
+            Action::Research(ref name) => {
+                let index = player.research.iter().position(|research| {
+                    research.upgrade.name == *name && !research.is_maxed() && research.next_cost() <= blackboard.minerals
+                });
+                match index {
+                    Some(index) => {
+                        blackboard.minerals -= player.research[index].next_cost();
+                        blackboard.commands.push(Command::Research(index));
+                        true
+                    }
+                    None => false,
+                }
+            }
+            Action::Hold(position) => blackboard.order(Order::Hold(position)),
+            Action::Advance => blackboard.order(Order::Advance),
+            Action::Retreat => blackboard.order(Order::Retreat),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use data::GameData;
+    use setup::Setup;
+    use player::Controltype;
+    use simulation::Simulation;
+
+    fn simulation() -> Simulation {
+        let data = GameData::bundled();
+        let mut setup = Setup::from_name("1v1").unwrap();
+        setup.set_factions(&["zerg", "terran"]);
+        Simulation::new(&setup, &data, 1).unwrap()
+    }
+
+    /// A zerg player with the passed minerals and its terran opponent
+    fn players(minerals: u32) -> (Player, Player) {
+        let mut players = simulation().players;
+        players[0].minerals = minerals;
+        let opponent = players.pop().unwrap();
+        (players.pop().unwrap(), opponent)
+    }
+
+    fn tree(root: &str) -> BehaviorTree {
+        serde_json::from_str(&format!(r#"{{"root": {}}}"#, root)).unwrap()
+    }
+
+    #[test]
+    fn spawns_only_what_it_can_afford() {
+        let tree = tree(r#"{"type": "action", "action": {"spawn": "ling"}}"#);
+        let (player, opponent) = players(200);
+        assert_eq!(tree.decide(&player, &[&opponent]), vec![Command::Spawn(UnitKind::Ling)]);
+        let (player, opponent) = players(199);
+        assert_eq!(tree.decide(&player, &[&opponent]), vec![]);
+    }
+
+    #[test]
+    fn selector_falls_back_to_the_next_child() {
+        let tree = tree(r#"{"type": "selector", "children": [
+            {"type": "action", "action": {"spawn": "bane"}},
+            {"type": "action", "action": {"spawn": "ling"}}
+        ]}"#);
+        let (player, opponent) = players(300);
+        let (commands, trace) = tree.decide_traced(&player, &[&opponent]);
+        assert_eq!(commands, vec![Command::Spawn(UnitKind::Ling)]);
+        let nodes: Vec<(usize, bool)> = trace.iter().map(|node| (node.depth, node.success)).collect();
+        assert_eq!(nodes, vec![(0, true), (1, false), (1, true)]);
+    }
+
+    #[test]
+    fn sequence_stops_at_the_first_failure() {
+        let tree = tree(r#"{"type": "sequence", "name": "build", "children": [
+            {"type": "action", "action": {"spawn": "ling"}},
+            {"type": "action", "action": {"spawn": "hydra"}},
+            {"type": "action", "action": {"spawn": "ling"}}
+        ]}"#);
+        let (player, opponent) = players(400);
+        let (commands, trace) = tree.decide_traced(&player, &[&opponent]);
+        assert_eq!(commands, tree.decide(&player, &[&opponent]));
+        assert_eq!(commands, vec![Command::Spawn(UnitKind::Ling)]);
+        assert_eq!(trace.len(), 3);
+        assert_eq!(trace[0].label, "build");
+        assert!(!trace[0].success);
+    }
+
+    #[test]
+    fn orders_only_change_the_current_order() {
+        let (player, opponent) = players(0);
+        assert_eq!(player.order, Order::Advance);
+        assert_eq!(tree(r#"{"type": "action", "action": "advance"}"#).decide(&player, &[&opponent]), vec![]);
+        let tree = tree(r#"{"type": "sequence", "children": [
+            {"type": "action", "action": "retreat"},
+            {"type": "condition", "condition": "retreating"},
+            {"type": "action", "action": "retreat"}
+        ]}"#);
+        assert_eq!(tree.decide(&player, &[&opponent]), vec![Command::Retreat]);
+    }
+
+    #[test]
+    fn simulation_keeps_the_trace_of_the_last_decision() {
+        let mut sim = simulation();
+        sim.players[0].controltype = Controltype::BehaviorTree(tree(r#"{"type": "action", "action": {"spawn": "ling"}}"#));
+        sim.tick().unwrap();
+        assert_eq!(sim.trace(0), &[Trace{depth: 0, label: String::from("Spawn(Ling)"), success: false}][..]);
+        assert!(sim.trace(1).is_empty());
+    }
+}
@@ -0,0 +1,376 @@
+use ggez::{GameResult, GameError};
+use serde_json;
+
+use std::io::{BufRead, BufReader};
+use std::net::{Shutdown, TcpStream};
+use std::process::{self, Child, ChildStdin, Stdio};
+use std::sync::mpsc::{Receiver, RecvTimeoutError};
+use std::time::Duration;
+
+use player::{Player, Command};
+use gameobject::{UnitKind, Order};
+use constants::TICKS_PER_SECOND;
+use jsonlines;
+
+
+/// Version of the protocol, sent to every bot in its `hello` message
+pub const PROTOCOL_VERSION: u32 = 1;
+
+/// How to run a bot, and how often and how long the match waits for its answers
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct BotParams{
+    /// The program to run, looked up like a shell would.
+    /// Without one, the player waits for a bot to claim it over TCP, see `server::Server`
+    pub command: Option<String>,
+    #[serde(default)]
+    pub args: Vec<String>,
+    /// Seconds between two observations sent to the bot
+    #[serde(default = "default_interval")]
+    pub interval: f32,
+    /// Milliseconds the bot may take to answer an observation before it forfeits
+    #[serde(default = "default_timeout")]
+    pub timeout: u64,
+}
+
+fn default_interval() -> f32 {
+    1.0
+}
+
+fn default_timeout() -> u64 {
+    1000
+}
+
+impl BotParams {
+    /// Returns the number of ticks between two observations
+    pub fn interval_ticks(&self) -> u64 {
+        ((self.interval*TICKS_PER_SECOND as f32) as u64).max(1)
+    }
+}
+
+/// A message from the match to a bot, written as a single line of json
+#[derive(Serialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+enum Message<'a>{
+    /// Sent once before the first observation
+    Hello{
+        version: u32,
+        /// Index of the bot's player in the `players` of every observation
+        player: usize,
+        team: usize,
+        faction: &'a str,
+        roster: Vec<RosterEntry>,
+        upgrades: Vec<UpgradeEntry<'a>>,
+        morphs: Vec<MorphEntry>,
+        abilities: &'a [String],
+        lanes: usize,
+    },
+    /// Sent every `interval` seconds, the bot answers with its commands
+    Observation{
+        tick: u64,
+        players: Vec<PlayerView<'a>>,
+    },
+    /// Sent once the match is over, right before the bot is terminated
+    End{
+        winner: Option<usize>,
+    },
+}
+
+#[derive(Serialize)]
+struct RosterEntry{
+    kind: UnitKind,
+    cost: u32,
+    structure: bool,
+}
+
+#[derive(Serialize)]
+struct UpgradeEntry<'a>{
+    name: &'a str,
+    /// Cost of the next level
+    cost: u32,
+    level: u32,
+    max_level: u32,
+}
+
+#[derive(Serialize)]
+struct MorphEntry{
+    from: UnitKind,
+    into: UnitKind,
+    cost: u32,
+}
+
+#[derive(Serialize)]
+struct PlayerView<'a>{
+    team: usize,
+    faction: &'a str,
+    minerals: u32,
+    base_hp: f32,
+    order: Order,
+    /// Progress of each upgrade, in the order of the `hello`
+    research: Vec<ResearchView>,
+    units: Vec<UnitView>,
+}
+
+#[derive(Serialize)]
+struct ResearchView{
+    level: u32,
+    /// Cost of the next level
+    cost: u32,
+}
+
+#[derive(Serialize)]
+struct UnitView{
+    kind: UnitKind,
+    lane: usize,
+    /// Measured like `GameChar::advance()`, from the unit's own base
+    advance: f32,
+    hp: f32,
+}
+
+/// A command from a bot, its answers being a json array of them on a single line.
+/// Scripts return their commands in the same format, see `script::Script`
+#[derive(Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum Reply{
+    Spawn{kind: UnitKind},
+    Research{index: usize},
+    Morph{index: usize},
+    Advance,
+    Hold{position: f32},
+    Retreat,
+    Rally{position: Option<f32>},
+    Ability{index: usize},
+    Lane{index: usize},
+}
+
+impl Reply {
+    /// Returns the command the reply stands for
+    pub fn command(&self) -> Command {
+        match *self {
+            Reply::Spawn{kind} => Command::Spawn(kind),
+            Reply::Research{index} => Command::Research(index),
+            Reply::Morph{index} => Command::Morph(index),
+            Reply::Advance => Command::Advance,
+            Reply::Hold{position} => Command::Hold(position),
+            Reply::Retreat => Command::Retreat,
+            Reply::Rally{position} => Command::Rally(position),
+            Reply::Ability{index} => Command::Ability(index),
+            Reply::Lane{index} => Command::Lane(index),
+        }
+    }
+}
+
+/// Where the messages to a bot are written to
+#[derive(Debug)]
+enum Output{
+    Pipe(ChildStdin),
+    Socket(TcpStream),
+}
+
+/// An external program controlling a player, talking json lines over its stdin and stdout or over a TCP connection.
+/// A started program is terminated and a connection closed when the `Bot` is dropped
+#[derive(Debug)]
+pub struct Bot{
+    child: Option<Child>,
+    output: Output,
+    /// Lines the bot has written, read on a separate thread so answers can time out
+    lines: Receiver<String>,
+    /// Whether the bot has been sent its `hello`
+    greeted: bool,
+}
+
+impl Bot {
+    /// Starts the program of the passed params
+    pub fn start(params: &BotParams) -> GameResult<Bot> {
+        let command = params.command.as_ref()
+            .ok_or_else(|| GameError::UnknownError(String::from("no bot has claimed the player")))?;
+        let mut child = process::Command::new(command)
+            .args(&params.args)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::inherit())
+            .spawn()
+            .map_err(|e| GameError::UnknownError(format!("could not start {}: {}", command, e)))?;
+        let stdin = child.stdin.take().unwrap();
+        let stdout = child.stdout.take().unwrap();
+        Ok(Bot::with_input(Some(child), Output::Pipe(stdin), BufReader::new(stdout)))
+    }
+
+    /// Returns a bot talking over the passed connection, reading the rest of its lines from `input`
+    pub fn connected<R: BufRead + Send + 'static>(stream: TcpStream, input: R) -> Bot {
+        Bot::with_input(None, Output::Socket(stream), input)
+    }
+
+    fn with_input<R: BufRead + Send + 'static>(child: Option<Child>, output: Output, input: R) -> Bot {
+        Bot{
+            child: child,
+            output: output,
+            lines: jsonlines::lines(input),
+            greeted: false,
+        }
+    }
+
+    /// Introduces the player at the passed index to its bot
+    fn greet(&mut self, players: &[Player], player: usize, lanes: usize) -> GameResult<()> {
+        let me = &players[player];
+        self.send(&Message::Hello{
+            version: PROTOCOL_VERSION,
+            player: player,
+            team: me.team,
+            faction: &me.faction,
+            roster: me.roster.iter().map(|def| RosterEntry{kind: def.kind, cost: def.stats.cost, structure: def.structure}).collect(),
+            upgrades: me.research.iter().map(|research| UpgradeEntry{
+                name: &research.upgrade.name,
+                cost: research.next_cost(),
+                level: research.level,
+                max_level: research.upgrade.max_level,
+            }).collect(),
+            morphs: me.morphs.iter().map(|morph| MorphEntry{from: morph.from, into: morph.into, cost: morph.cost}).collect(),
+            abilities: &me.abilities,
+            lanes: lanes,
+        })?;
+        self.greeted = true;
+        Ok(())
+    }
+
+    fn send(&mut self, message: &Message) -> GameResult<()> {
+        let written = match self.output {
+            Output::Pipe(ref mut stdin) => jsonlines::write(stdin, message),
+            Output::Socket(ref mut stream) => jsonlines::write(stream, message),
+        };
+        written.map_err(|e| GameError::UnknownError(format!("bot stopped listening: {}", e)))
+    }
+
+    /// Sends the current match to the bot of the player at the passed index, greeting it first if it is new,
+    /// and returns the commands it answers with.
+    /// Fails if the bot doesn't answer in time, has exited or answers with anything but a json array of commands
+    pub fn decide(&mut self, players: &[Player], player: usize, lanes: usize, tick: u64, timeout: u64) -> GameResult<Vec<Command>> {
+        if !self.greeted {
+            self.greet(players, player, lanes)?;
+        }
+        self.send(&Message::Observation{
+            tick: tick,
+            players: players.iter().map(|player| PlayerView{
+                team: player.team,
+                faction: &player.faction,
+                minerals: player.minerals,
+                base_hp: player.base.hp,
+                order: player.order,
+                research: player.research.iter().map(|research| ResearchView{
+                    level: research.level,
+                    cost: research.next_cost(),
+                }).collect(),
+                units: player.units.iter().map(|unit| UnitView{
+                    kind: unit.kind,
+                    lane: unit.lane(),
+                    advance: unit.advance(),
+                    hp: unit.stats.hp,
+                }).collect(),
+            }).collect(),
+        })?;
+        let line = match self.lines.recv_timeout(Duration::from_millis(timeout)) {
+            Ok(line) => line,
+            Err(RecvTimeoutError::Timeout) => return Err(GameError::UnknownError(format!("no answer within {}ms", timeout))),
+            Err(RecvTimeoutError::Disconnected) => return Err(GameError::UnknownError(String::from("bot has exited"))),
+        };
+        let replies: Vec<Reply> = serde_json::from_str(&line)
+            .map_err(|e| GameError::UnknownError(format!("invalid answer {:?}: {}", line, e)))?;
+        Ok(replies.iter().map(Reply::command).collect())
+    }
+
+    /// Tells the bot the match is over. It is terminated once dropped
+    pub fn finish(&mut self, winner: Option<usize>) {
+        let _ = self.send(&Message::End{winner: winner});
+    }
+}
+
+impl Drop for Bot {
+    fn drop(&mut self) {
+        if let Some(ref mut child) = self.child {
+            let _ = child.kill();
+            let _ = child.wait();
+        }
+        if let Output::Socket(ref stream) = self.output {
+            let _ = stream.shutdown(Shutdown::Both);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use data::GameData;
+    use player::Controltype;
+    use setup::Setup;
+    use simulation::Simulation;
+    use std::io::{BufRead, Write};
+    use std::net::TcpListener;
+    use std::thread;
+
+    /// Returns a bot talking over a localhost connection, with the bot's end of it
+    fn connected() -> (Bot, TcpStream) {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let client = TcpStream::connect(listener.local_addr().unwrap()).unwrap();
+        let (stream, _) = listener.accept().unwrap();
+        let input = BufReader::new(stream.try_clone().unwrap());
+        (Bot::connected(stream, input), client)
+    }
+
+    fn simulation() -> Simulation {
+        Simulation::new(&Setup::from_name("aivai").unwrap(), &GameData::bundled(), 1).unwrap()
+    }
+
+    fn error(result: GameResult<Vec<Command>>) -> String {
+        match result {
+            Err(GameError::UnknownError(e)) => e,
+            result => panic!("expected the bot to fail, got {:?}", result),
+        }
+    }
+
+    #[test]
+    fn silent_bot_times_out() {
+        let (mut bot, _client) = connected();
+        let sim = simulation();
+        assert_eq!(error(bot.decide(&sim.players, 0, sim.lanes, 0, 50)), "no answer within 50ms");
+    }
+
+    #[test]
+    fn answer_that_is_no_array_is_invalid() {
+        let (mut bot, client) = connected();
+        let sim = simulation();
+        let mut lines = BufReader::new(client.try_clone().unwrap()).lines();
+        let mut output = client;
+        let answered = thread::spawn(move || {
+            // The hello and the observation
+            lines.next();
+            lines.next();
+            writeln!(output, r#"{{"type": "advance"}}"#).unwrap();
+        });
+        assert!(error(bot.decide(&sim.players, 0, sim.lanes, 0, 1000)).starts_with("invalid answer"));
+        answered.join().unwrap();
+    }
+
+    #[test]
+    fn closed_connection_means_the_bot_has_exited() {
+        let (mut bot, client) = connected();
+        client.shutdown(Shutdown::Write).unwrap();
+        let sim = simulation();
+        assert_eq!(error(bot.decide(&sim.players, 0, sim.lanes, 0, 1000)), "bot has exited");
+    }
+
+    #[test]
+    fn failing_bot_forfeits() {
+        let (bot, client) = connected();
+        client.shutdown(Shutdown::Write).unwrap();
+        let mut sim = simulation();
+        sim.players[0].controltype = Controltype::External(BotParams{
+            command: None,
+            args: Vec::new(),
+            interval: 0.0,
+            timeout: 1000,
+        });
+        sim.attach(0, bot);
+        sim.tick().unwrap();
+        assert_eq!(sim.players[0].base.hp, 0.0);
+        assert!(sim.players[1].is_alive());
+    }
+}
@@ -1,17 +1,70 @@
+use std::cell::Cell;
+
 /// The following constants globally scale values such as speed, damage and size/position on the map
 /// They can be tweaked in order to make the game easier to observe by a human player
 /// 
 /// Please note that the realtionship of movement speed and damage scale constants severely affect
 /// the balance between melee and ranged units
 /// 
-/// Constant that scales the movement speed of units
+/// Constant that scales the movement speed of units, the default of `SimConfig::movement_speed`
 pub const MOVEMENT_SPEED: f32 = 0.01;
 
-/// Constant that scales the damage each unit does
+/// Constant that scales the damage each unit does, the default of `SimConfig::damage_scale`
 pub const DAMAGE_SCALE: f32 = 0.01;
 
-/// Constant that scales the positioning of units and bases
+/// Minimum (unscaled) damage a hit deals, no matter how much armor the target has
+pub const MIN_DAMAGE: f32 = 0.5;
+
+/// Energy each unit regenerates per second, up to its maximum energy
+pub const ENERGY_REGENERATION: f32 = 1.0;
+
+/// Constant that scales the positioning of units and bases, the default of `SimConfig::map_scale`
 pub const MAP_SCALE: f32 =  500.0;
 
+/// Distance from the own base at which structures are built, as a fraction of the map
+pub const STRUCTURE_POSITION: f32 = 0.08;
+
 /// Constant that currently has no real use case
-pub const SPEED: f32 = 8.0;
\ No newline at end of file
+pub const SPEED: f32 = 8.0;
+
+/// Number of simulation ticks per second. The simulation advances in fixed ticks,
+/// independent of the frame rate, so matches can be reproduced
+pub const TICKS_PER_SECOND: u32 = 60;
+
+/// Headless games, e.g. of sweeps and tournaments, that haven't been decided after this many seconds count as a draw
+pub const GAME_TIMEOUT: f32 = 600.0;
+
+/// The scale constants that affect the balance, changeable at runtime, e.g. by a parameter sweep.
+/// Every thread simulates with its own config, starting out with the constants above
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct SimConfig{
+    pub movement_speed: f32,
+    pub damage_scale: f32,
+    pub map_scale: f32,
+}
+
+impl Default for SimConfig {
+    fn default() -> SimConfig {
+        SimConfig{
+            movement_speed: MOVEMENT_SPEED,
+            damage_scale: DAMAGE_SCALE,
+            map_scale: MAP_SCALE,
+        }
+    }
+}
+
+thread_local!{
+    static CONFIG: Cell<SimConfig> = Cell::new(SimConfig::default());
+}
+
+impl SimConfig {
+    /// Returns the config the current thread simulates with
+    pub fn current() -> SimConfig {
+        CONFIG.with(|config| config.get())
+    }
+
+    /// Makes the current thread simulate with this config
+    pub fn apply(self) {
+        CONFIG.with(|config| config.set(self))
+    }
+}
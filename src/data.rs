@@ -0,0 +1,49 @@
+use ggez::{Context, GameResult, GameError};
+use serde_json;
+
+use std::io::Read;
+use std::path;
+
+use faction::Faction;
+use objective::ObjectiveDef;
+use dice::Randomness;
+
+
+/// Game balance data that is loaded from a json file at launch,
+/// so it can be tweaked without recompiling
+#[derive(Debug, Clone, Deserialize)]
+pub struct GameData{
+    pub factions: Vec<Faction>,
+    #[serde(default)]
+    pub objectives: Vec<ObjectiveDef>,
+    #[serde(default)]
+    pub randomness: Randomness,
+}
+
+impl GameData {
+    /// Loads the `GameData` from the specified path in the ggez filesystem
+    pub fn load<P: AsRef<path::Path>>(ctx: &mut Context, path: P) -> GameResult<GameData> {
+        let file = ctx.filesystem.open(path)?;
+        GameData::from_reader(file)
+    }
+
+    /// Returns the faction with the passed name, ignoring case
+    pub fn faction(&self, name: &str) -> GameResult<&Faction> {
+        self.factions.iter()
+            .find(|faction| faction.name.to_lowercase() == name.to_lowercase())
+            .ok_or_else(|| GameError::ResourceLoadError(format!("unknown faction {}", name)))
+    }
+
+    /// Parses the `GameData` from any reader that provides json
+    pub fn from_reader<R: Read>(reader: R) -> GameResult<GameData> {
+        serde_json::from_reader(reader)
+            .map_err(|e| GameError::ResourceLoadError(format!("invalid game data: {}", e)))
+    }
+
+    /// Returns the game data shipped in `assets/`, for tests
+    #[cfg(test)]
+    pub fn bundled() -> GameData {
+        let file = ::std::fs::File::open(concat!(env!("CARGO_MANIFEST_DIR"), "/assets/gamedata.json")).unwrap();
+        GameData::from_reader(file).unwrap()
+    }
+}
@@ -0,0 +1,131 @@
+/// Settings of the random elements of a match, as defined in the data file.
+/// All of them are turned off unless specified, the shipped data file turns on a little of each
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize)]
+pub struct Randomness{
+    /// Each hit deals up to this fraction more or less than the attacker's damage
+    #[serde(default)]
+    pub damage_variance: f32,
+    /// Chance of each attack to miss its target
+    #[serde(default)]
+    pub miss_chance: f32,
+    /// Newly spawned units start up to this far away from their base, as a fraction of the map
+    #[serde(default)]
+    pub spawn_jitter: f32,
+}
+
+
+/// The only source of randomness in a match: a seeded pseudo random number generator (SplitMix64),
+/// so any match can be reproduced from its seed
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Dice{
+    seed: u64,
+    state: u64,
+    pub randomness: Randomness,
+}
+
+impl Dice {
+    /// Returns new `Dice` starting from the passed seed
+    pub fn new(seed: u64, randomness: Randomness) -> Dice {
+        Dice{
+            seed: seed,
+            state: seed,
+            randomness: randomness,
+        }
+    }
+
+    /// Returns the seed the dice started from
+    pub fn seed(&self) -> u64 {
+        self.seed
+    }
+
+    fn next_u64(&mut self) -> u64 {
+        self.state = self.state.wrapping_add(0x9E37_79B9_7F4A_7C15);
+        let mut z = self.state;
+        z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+        z ^ (z >> 31)
+    }
+
+    /// Returns a number in [0.0, 1.0)
+    pub fn roll(&mut self) -> f32 {
+        (self.next_u64() >> 40) as f32 / (1u64 << 24) as f32
+    }
+
+    /// Returns true if an attack misses
+    pub fn misses(&mut self) -> bool {
+        self.randomness.miss_chance > 0.0 && self.roll() < self.randomness.miss_chance
+    }
+
+    /// Returns the passed damage, varied by up to `damage_variance` in either direction
+    pub fn damage(&mut self, damage: f32) -> f32 {
+        if self.randomness.damage_variance <= 0.0 {
+            return damage;
+        }
+        damage*(1.0 + self.randomness.damage_variance*(2.0*self.roll() - 1.0))
+    }
+
+    /// Returns how far away from its base a newly spawned unit starts
+    pub fn jitter(&mut self) -> f32 {
+        if self.randomness.spawn_jitter <= 0.0 {
+            return 0.0;
+        }
+        self.randomness.spawn_jitter*self.roll()
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn randomness() -> Randomness {
+        Randomness{
+            damage_variance: 0.1,
+            miss_chance: 0.05,
+            spawn_jitter: 0.02,
+        }
+    }
+
+    #[test]
+    fn same_seed_rolls_the_same() {
+        let mut a = Dice::new(42, randomness());
+        let mut b = Dice::new(42, randomness());
+        for _ in 0..100 {
+            assert_eq!(a.roll(), b.roll());
+            assert_eq!(a.damage(10.0), b.damage(10.0));
+            assert_eq!(a.misses(), b.misses());
+        }
+    }
+
+    #[test]
+    fn different_seeds_roll_differently() {
+        let mut a = Dice::new(1, randomness());
+        let mut b = Dice::new(2, randomness());
+        let a: Vec<f32> = (0..10).map(|_| a.roll()).collect();
+        let b: Vec<f32> = (0..10).map(|_| b.roll()).collect();
+        assert_ne!(a, b);
+    }
+
+    #[test]
+    fn rolls_stay_within_their_bounds() {
+        let mut dice = Dice::new(7, randomness());
+        for _ in 0..1000 {
+            let roll = dice.roll();
+            assert!((0.0..1.0).contains(&roll));
+            let damage = dice.damage(10.0);
+            assert!((9.0..=11.0).contains(&damage));
+            let jitter = dice.jitter();
+            assert!((0.0..=0.02).contains(&jitter));
+        }
+    }
+
+    #[test]
+    fn without_randomness_nothing_varies() {
+        let mut dice = Dice::new(7, Randomness::default());
+        for _ in 0..100 {
+            assert!(!dice.misses());
+            assert_eq!(dice.damage(10.0), 10.0);
+            assert_eq!(dice.jitter(), 0.0);
+        }
+    }
+}
@@ -0,0 +1,179 @@
+use ggez::{GameResult, GameError};
+use serde_json;
+
+use std::fs::File;
+
+use player::{Controltype, AiParams};
+use data::GameData;
+use dice::Dice;
+use tournament::{Tournament, Entrant, Ratings, Schedule};
+
+
+/// A set of `SimpleAI` thresholds, together with the share of its games it scored in the last generation
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Genome{
+    pub params: AiParams,
+    #[serde(default)]
+    pub fitness: f32,
+}
+
+/// Controllers evolving over generations: every generation plays a round robin between its genomes,
+/// the best of them survive and the rest is replaced by mutated copies of the better half
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Population{
+    /// Number of generations evaluated before this one
+    pub generation: u32,
+    /// Faction every genome plays
+    pub faction: String,
+    /// Best first once evaluated
+    pub genomes: Vec<Genome>,
+}
+
+impl Population {
+    /// Returns a first generation of `size` genomes: the default `SimpleAI` and mutations of it
+    pub fn new(size: usize, faction: &str, mutation: f32, dice: &mut Dice) -> Population {
+        let genomes = (0..size.max(2)).map(|i| Genome{
+            params: if i == 0 {AiParams::default()} else {mutate(&AiParams::default(), mutation, dice)},
+            fitness: 0.0,
+        }).collect();
+        Population{
+            generation: 0,
+            faction: faction.to_string(),
+            genomes: genomes,
+        }
+    }
+
+    /// Reads a population from the json file at the passed path
+    pub fn read(path: &str) -> GameResult<Population> {
+        let file = File::open(path)?;
+        serde_json::from_reader(file)
+            .map_err(|e| GameError::ResourceLoadError(format!("invalid population: {}", e)))
+    }
+
+    /// Writes the population to the json file at the passed path
+    pub fn write(&self, path: &str) -> GameResult<()> {
+        let file = File::create(path)?;
+        serde_json::to_writer_pretty(file, self)
+            .map_err(|e| GameError::UnknownError(format!("could not write population: {}", e)))
+    }
+
+    /// Plays a round robin between all genomes on the passed maps, `seeds` games per map and side,
+    /// and sorts them by the share of their games they scored
+    pub fn evaluate(&mut self, data: &GameData, maps: &[String], seeds: u32, seed: u64) -> GameResult<()> {
+        let tournament = Tournament{
+            entrants: self.genomes.iter().enumerate().map(|(i, genome)| Entrant{
+                name: format!("Genome {}", i + 1),
+                faction: self.faction.clone(),
+                controller: Controltype::SimpleAI(genome.params),
+            }).collect(),
+            maps: maps.to_vec(),
+            seeds: seeds,
+            seed: seed,
+        };
+        // Ratings of genomes don't outlive their generation
+        let points = tournament.run(data, Schedule::RoundRobin, &mut Ratings::default())?;
+        let games = ((self.genomes.len() - 1)*maps.len()*seeds as usize*2).max(1) as f32;
+        for (genome, points) in self.genomes.iter_mut().zip(points) {
+            genome.fitness = points/games;
+        }
+        self.genomes.sort_by(|a, b| b.fitness.partial_cmp(&a.fitness).unwrap());
+        Ok(())
+    }
+
+    /// Replaces the evaluated population by the next generation. The best quarter survives unchanged,
+    /// every other genome is a mutation of the fitter of two random genomes of the better half
+    pub fn breed(&mut self, mutation: f32, dice: &mut Dice) {
+        let elite = (self.genomes.len()/4).max(1);
+        let parents = (self.genomes.len()/2).max(1);
+        let pick = |dice: &mut Dice| ((dice.roll()*parents as f32) as usize).min(parents - 1);
+        for i in elite..self.genomes.len() {
+            // Genomes are sorted, the lower index is the fitter one
+            let parent = pick(dice).min(pick(dice));
+            self.genomes[i] = Genome{
+                params: mutate(&self.genomes[parent].params, mutation, dice),
+                fitness: 0.0,
+            };
+        }
+        self.generation += 1;
+    }
+
+    /// Returns the average fitness of the evaluated population
+    pub fn mean_fitness(&self) -> f32 {
+        self.genomes.iter().map(|genome| genome.fitness).sum::<f32>()/self.genomes.len().max(1) as f32
+    }
+}
+
+/// Returns a copy of the passed params with every threshold moved randomly,
+/// by up to `strength` times its spread in either direction
+fn mutate(params: &AiParams, strength: f32, dice: &mut Dice) -> AiParams {
+    let mut nudge = |value: f32, spread: f32, max: f32| (value + strength*spread*(2.0*dice.roll() - 1.0)).max(0.0).min(max);
+    let mut params = AiParams{
+        retreat_ratio: nudge(params.retreat_ratio, 0.5, 2.0),
+        defense_front: nudge(params.defense_front, 0.3, 1.0),
+        research_ratio: nudge(params.research_ratio, 0.5, 3.0),
+        counter_ratio: nudge(params.counter_ratio, 0.5, 3.0),
+        reaction: nudge(params.reaction, 1.0, 5.0),
+        reserve: nudge(params.reserve as f32, 100.0, 1000.0) as u32,
+        ..*params
+    };
+    if dice.roll() < strength {
+        params.max_defense = if dice.roll() < 0.5 {params.max_defense.saturating_sub(1)} else {params.max_defense + 1};
+    }
+    if dice.roll() < strength/2.0 {
+        params.hold_fields = !params.hold_fields;
+    }
+    params
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use dice::Randomness;
+
+    fn dice() -> Dice {
+        Dice::new(11, Randomness::default())
+    }
+
+    #[test]
+    fn mutations_stay_within_bounds() {
+        let mut dice = dice();
+        let mut params = AiParams::default();
+        for _ in 0..500 {
+            params = mutate(&params, 3.0, &mut dice);
+            assert!((0.0..=2.0).contains(&params.retreat_ratio));
+            assert!((0.0..=1.0).contains(&params.defense_front));
+            assert!((0.0..=3.0).contains(&params.research_ratio));
+            assert!((0.0..=3.0).contains(&params.counter_ratio));
+            assert!((0.0..=5.0).contains(&params.reaction));
+            assert!(params.reserve <= 1000);
+        }
+    }
+
+    #[test]
+    fn elite_quarter_survives_breeding() {
+        let mut dice = dice();
+        let mut population = Population::new(8, "zerg", 0.5, &mut dice);
+        for (i, genome) in population.genomes.iter_mut().enumerate() {
+            genome.fitness = 1.0 - i as f32/8.0;
+        }
+        let before = population.genomes.clone();
+        population.breed(0.5, &mut dice);
+        assert_eq!(population.generation, 1);
+        assert_eq!(population.genomes.len(), 8);
+        for (bred, old) in population.genomes.iter().zip(&before).take(2) {
+            assert_eq!(bred.params, old.params);
+            assert_eq!(bred.fitness, old.fitness);
+        }
+        assert!(population.genomes[2..].iter().all(|genome| genome.fitness == 0.0));
+    }
+
+    #[test]
+    fn evaluation_sorts_best_first() {
+        let mut dice = dice();
+        let mut population = Population::new(3, "zerg", 0.5, &mut dice);
+        population.evaluate(&GameData::bundled(), &[String::from("1v1")], 1, 5).unwrap();
+        let fitness: Vec<f32> = population.genomes.iter().map(|genome| genome.fitness).collect();
+        assert!(fitness.iter().all(|fitness| (0.0..=1.0).contains(fitness)));
+        assert!(fitness.windows(2).all(|pair| pair[0] >= pair[1]));
+    }
+}
@@ -0,0 +1,25 @@
+use upgrade::Upgrade;
+use gameobject::{Morph, UnitDef};
+
+
+/// A playable faction with its own base, units, upgrades and morphs, as defined in the data file
+#[derive(Debug, Clone, Deserialize)]
+pub struct Faction{
+    pub name: String,
+    pub base: BaseDef,
+    pub units: Vec<UnitDef>,
+    #[serde(default)]
+    pub upgrades: Vec<Upgrade>,
+    #[serde(default)]
+    pub morphs: Vec<Morph>,
+    /// Color the faction's units and base are tinted with, so factions sharing sprites can be told apart
+    #[serde(default)]
+    pub tint: Option<[f32; 3]>,
+}
+
+/// The definition of a faction's `Base`
+#[derive(Debug, Clone, Deserialize)]
+pub struct BaseDef{
+    pub sprite: String,
+    pub hp: f32,
+}
@@ -1,206 +1,508 @@
 use traits::{Position};
 use gamestate::{Side};
-use constants::{MOVEMENT_SPEED, DAMAGE_SCALE, MAP_SCALE};
+use constants::{SimConfig, DAMAGE_SCALE, MIN_DAMAGE, ENERGY_REGENERATION, STRUCTURE_POSITION, TICKS_PER_SECOND};
+use ability::{Ability, AbilityState, AbilityEffect};
+use status::{StatusEffect, StatusKind, Status};
+use objective::Objective;
+use setup::LaneEnd;
+use faction::BaseDef;
+use dice::Dice;
 
-use ggez::graphics;
-use ggez::{Context, GameResult};
-use ggez;
 
 
 /// The stats of a character
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct CharStats{
     pub cost: u32,
     pub hp: f32,
-    damage: f32,
-    speed: f32,
-    range: f32,
+    pub damage: f32,
+    pub speed: f32,
+    pub range: f32,
+    #[serde(default)]
+    pub armor: f32,
+    /// Maximum energy, used by abilities
+    #[serde(default)]
+    pub energy: f32,
     targets: usize,   
 }
 
 
+/// The definition of a kind of `GameChar`, as found in the data file
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct UnitDef{
+    pub kind: UnitKind,
+    pub name: String,
+    /// Sprite used for units of the player on the left side
+    pub sprite_left: String,
+    /// Sprite used for units of the player on the right side
+    pub sprite_right: String,
+    pub stats: CharStats,
+    #[serde(default)]
+    pub abilities: Vec<Ability>,
+    /// Status effects every attack of the unit inflicts on its target
+    #[serde(default)]
+    pub on_hit: Vec<StatusEffect>,
+    /// Structures are built in front of the own base and never move, no matter the order
+    #[serde(default)]
+    pub structure: bool,
+    /// Maximum number of units of this kind a player can have at the same time
+    #[serde(default)]
+    pub limit: Option<u32>,
+    /// Kinds of enemy units this kind is good against, used by the AI to pick counters
+    #[serde(default)]
+    pub counters: Vec<UnitKind>,
+}
+
 
 
 
 
+
+/// The different kinds of `GameChar`s
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum UnitKind{
+    Ling,
+    Hydra,
+    Bane,
+    Spine,
+    Spore,
+    Marine,
+    Marauder,
+    Tank,
+}
+
 /// A `GameChar` represents what once would consider a `unit` in RTS. 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct GameChar{
     pub name: String,
+    pub kind: UnitKind,
     pub stats: CharStats,
     position: f32,
-    pub sprite: graphics::Image,
+    /// Path of the sprite in the ggez filesystem
+    pub sprite: String,
+    lane: usize,
     side: Side,
+    pub morphing: Option<Morphing>,
+    pub order: Order,
+    pub max_hp: f32,
+    pub energy: f32,
+    pub abilities: Vec<AbilityState>,
+    pub on_hit: Vec<StatusEffect>,
+    pub statuses: Vec<Status>,
+    pub structure: bool,
+}
+
+
+/// The movement order a `GameChar` follows whenever there is nothing to attack
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub enum Order{
+    /// Walk towards the enemy base
+    Advance,
+    /// Walk to the passed position, measured like `GameChar::advance()`, and stay there
+    Hold(f32),
+    /// Walk back to the own base without stopping to fight
+    Retreat,
+}
+
+
+/// A morph from one kind of `GameChar` into another, as defined in the data file
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Morph{
+    pub from: UnitKind,
+    pub into: UnitKind,
+    pub cost: u32,
+    /// Time in seconds the morph takes to complete
+    pub time: f32,
+}
+
+/// The progress of a `GameChar` that is currently morphing.
+/// Morphing units can neither move nor attack
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+pub struct Morphing{
+    pub into: UnitKind,
+    /// Time in seconds until the morph completes
+    pub remaining: f32,
 }
 
 
 
 impl GameChar{
-    /// Returns a GameChar that represents a zergling
-    /// Fast, cheap, meelee fighter
-    pub fn ling(ctx: &mut Context, side: Side) -> GameResult<GameChar>{
-        let mut sprite = graphics::Image::new(ctx, "/ggez_zergling_right.png")?;
-        let mut position = 1.0;
-        if side == Side::Left {
-            sprite = graphics::Image::new(ctx, "/ggez_zergling_left.png")?;
-            position = 0.0;
-        }
-        Ok(GameChar{
-            name: String::from("ling"),
-            stats: CharStats{
-                cost: 10,
-                speed: 2.0,
-                damage: 4.0,
-                hp: 10.0,
-                range: 3.0,
-                targets: 1,
-            },
+    /// Returns a GameChar as described by the passed definition, spawned at the passed lane end
+    pub fn new(def: &UnitDef, end: LaneEnd) -> GameChar{
+        let offset = if def.structure {STRUCTURE_POSITION} else {0.0};
+        let (sprite, position) = match end.side {
+            Side::Left => (def.sprite_left.clone(), offset),
+            Side::Right => (def.sprite_right.clone(), 1.0 - offset),
+        };
+        GameChar{
+            name: def.name.clone(),
+            kind: def.kind,
+            stats: def.stats.clone(),
             position: position,
             sprite: sprite,
-            side: side,
-        })
-        
-    }
-    /// Returns a GameChar that represents a hydra
-    /// Ranged damage
-    pub fn hydra(ctx: &mut Context, side: Side) -> GameResult<GameChar>{
-        let mut sprite = graphics::Image::new(ctx, "/ggez_hydra_right.png")?;
-        let mut position = 1.0;
-        if side == Side::Left {
-            sprite = graphics::Image::new(ctx, "/ggez_hydra_left.png")?;
-            position = 0.0;
-        }
-        Ok(GameChar{
-            name: String::from("hydra"),
-            stats: CharStats{
-                cost: 20,
-                speed: 1.0,
-                damage: 4.0,
-                hp: 12.0,
-                range: 150.0,
-                targets: 1,
-            },
-            position: position,
-            sprite: sprite,
-            side: side,
-        })
+            lane: end.lane,
+            side: end.side,
+            morphing: None,
+            order: Order::Advance,
+            max_hp: def.stats.hp,
+            energy: def.stats.energy,
+            abilities: def.abilities.iter().cloned().map(AbilityState::new).collect(),
+            on_hit: def.on_hit.clone(),
+            statuses: Vec::new(),
+            structure: def.structure,
+        }
+    }
 
+    /// Returns how far the `GameChar` has advanced from its own base towards the enemy base,
+    /// ranging from 0.0 at the own base to 1.0 at the enemy base
+    pub fn advance(&self) -> f32 {
+        match self.side {
+            Side::Left => self.position,
+            Side::Right => 1.0 - self.position,
+        }
     }
 
-    /// Returns a GameChar that represents a baneling
-    /// High splash damage, slow, decent HP
-    pub fn bane(ctx: &mut Context, side: Side) -> GameResult<GameChar>{
-        let mut sprite = graphics::Image::new(ctx, "/ggez_bane_right.png")?;
-        let mut position = 1.0;
+    /// Returns the kind the `GameChar` will have once it finished morphing
+    pub fn eventual_kind(&self) -> UnitKind {
+        match self.morphing {
+            Some(morphing) => morphing.into,
+            None => self.kind,
+        }
+    }
 
-        if side == Side::Left {
-            sprite = graphics::Image::new(ctx, "/ggez_bane_left.png")?;
-            position = 0.0;
+    /// Returns a new `GameChar` of the kind self is morphing into, at the position of self
+    pub fn morphed(&self, def: &UnitDef) -> GameChar {
+        let mut unit = GameChar::new(def, LaneEnd{lane: self.lane, side: self.side});
+        unit.position = self.position;
+        unit.order = self.order;
+        unit
+    }
 
+    /// Uses the ability with the passed name, if self has it and it is ready.
+    /// Returns true if the ability has been used
+    pub fn use_ability(&mut self, name: &str) -> bool {
+        if self.morphing.is_some() {
+            return false;
         }
-        Ok(GameChar{
-            name: String::from("bane"),
-            stats: CharStats{
-                cost: 30,
-                speed: 0.5,
-                damage: 10.0,
-                hp: 15.0,
-                range: 3.0,
-                targets: 1,
-            },
-            position: position,
-            sprite: sprite,
-            side: side,
+        let (energy, hp) = (self.energy, self.stats.hp);
+        match self.abilities.iter_mut().find(|state| state.ability.name == name) {
+            Some(ref mut state) if state.is_ready(energy) => {
+                //turning a toggled ability off is free, otherwise the unit must survive paying the hp cost
+                let hp_cost = if state.is_toggle() && state.is_active() {0.0} else {state.ability.hp};
+                if hp_cost > 0.0 && hp <= hp_cost {
+                    return false;
+                }
+                self.stats.hp -= hp_cost;
+                self.energy -= state.activate();
+                true
+            }
+            _ => false,
+        }
+    }
+
+    /// Returns the state of the ability with the passed name, if self has it
+    pub fn ability(&self, name: &str) -> Option<&AbilityState> {
+        self.abilities.iter().find(|state| state.ability.name == name)
+    }
+
+    /// Advances ability cooldowns and durations by `delta` seconds,
+    /// regenerating energy and the hp of burrowed units
+    pub fn update_abilities(&mut self, delta: f32){
+        self.energy = (self.energy + ENERGY_REGENERATION*delta).min(self.stats.energy);
+        if let Some(regeneration) = self.regeneration() {
+            self.stats.hp = (self.stats.hp + regeneration*delta).min(self.max_hp);
+        }
+        for state in &mut self.abilities {
+            state.update(delta);
+        }
+    }
+
+    /// Returns the effects of all abilities that are currently in effect
+    fn active_effects<'a>(&'a self) -> impl Iterator<Item = &'a AbilityEffect> + 'a {
+        self.abilities.iter().filter(|state| state.is_active()).map(|state| &state.ability.effect)
+    }
+
+    /// Returns true if self is burrowed, and thus can neither move, attack nor be attacked
+    pub fn is_burrowed(&self) -> bool {
+        self.regeneration().is_some()
+    }
+
+    /// Returns the hp regenerated per second if self is burrowed
+    fn regeneration(&self) -> Option<f32> {
+        self.active_effects().filter_map(|effect| match *effect {
+            AbilityEffect::Burrow{regeneration} => Some(regeneration),
+            _ => None,
+        }).next()
+    }
+
+    /// Returns true if self is deployed, and thus can't move
+    pub fn is_deployed(&self) -> bool {
+        self.active_effects().any(|effect| match *effect {
+            AbilityEffect::Deploy{..} => true,
+            _ => false,
+        })
+    }
+
+    /// Returns the side self fights for
+    pub fn side(&self) -> Side {
+        self.side
+    }
+
+    /// Moves self the passed distance away from its base, as a fraction of the map
+    pub fn nudge(&mut self, distance: f32){
+        match self.side{
+            Side::Left => self.position += distance,
+            Side::Right => self.position -= distance,
+        }
+    }
+
+    /// Returns the lane self walks along
+    pub fn lane(&self) -> usize {
+        self.lane
+    }
+
+    /// Returns true if self is boosted by an ability
+    pub fn is_boosted(&self) -> bool {
+        self.active_effects().any(|effect| match *effect {
+            AbilityEffect::Boost{..} => true,
+            _ => false,
         })
     }
 
+    /// Applies the passed status effect to self, following the effect's stacking rule
+    pub fn apply_status(&mut self, effect: &StatusEffect){
+        match self.statuses.iter_mut().find(|status| status.effect.name == effect.name) {
+            Some(status) => status.reapply(),
+            None => self.statuses.push(Status::new(effect)),
+        }
+    }
+
+    /// Advances all status effects by `delta` seconds, dealing acid damage
+    /// and removing effects that have run out
+    pub fn update_statuses(&mut self, delta: f32){
+        // Acid is given in damage per second at the default damage scale, and scales with it like hits do
+        let scale = SimConfig::current().damage_scale/DAMAGE_SCALE;
+        let mut acid = 0.0;
+        for status in &mut self.statuses {
+            if let StatusKind::Acid{damage} = status.effect.kind {
+                acid += damage*scale*status.stacks as f32*delta;
+            }
+            status.remaining -= delta;
+        }
+        self.stats.hp = self.stats.hp.zero_saturating_sub(acid);
+        self.statuses.retain(|status| status.remaining > 0.0);
+    }
+
+    /// Returns true if self is stunned, and thus can neither move nor attack
+    pub fn is_stunned(&self) -> bool {
+        self.statuses.iter().any(|status| status.effect.kind == StatusKind::Stun)
+    }
+
+    /// Returns the armor of self, lessened by armor shredding effects
+    fn armor(&self) -> f32 {
+        let shred: f32 = self.statuses.iter().map(|status| match status.effect.kind {
+            StatusKind::Shred{armor} => armor*status.stacks as f32,
+            _ => 0.0,
+        }).sum();
+        self.stats.armor - shred
+    }
+
     fn in_range<T: Position>(&self, other: &T) -> bool {
-        (self.position() - other.position()).abs() < self.stats.range
+        (self.position() - other.position()).abs() < self.range()
+    }
+
+    /// Returns the range of self, extended while deployed
+    fn range(&self) -> f32 {
+        let deployed: f32 = self.active_effects().map(|effect| match *effect {
+            AbilityEffect::Deploy{range, ..} => range,
+            _ => 0.0,
+        }).sum();
+        self.stats.range + deployed
     }
 
     /// Returns the damage value of the `GameChar`, multiplicated with
-    /// the DAMAGE_SCALE consant
+    /// the damage scale of the `SimConfig`, any active boosts, deploy modes and frenzies
     fn damage(&self) -> f32{
-        self.stats.damage*DAMAGE_SCALE
+        let boost: f32 = self.active_effects().map(|effect| match *effect {
+            AbilityEffect::Boost{damage, ..} | AbilityEffect::Deploy{damage, ..} => damage,
+            _ => 1.0,
+        }).product();
+        let frenzy: f32 = self.statuses.iter().map(|status| match status.effect.kind {
+            StatusKind::Frenzy{damage} => damage.powi(status.stacks as i32),
+            _ => 1.0,
+        }).product();
+        self.stats.damage*SimConfig::current().damage_scale*boost*frenzy
     }
 
     
 
     /// Returns the (horizontal) speed of the `GameChar`, multiplicated with
-    /// the MOVEMENT_SPEED constant
+    /// the movement speed of the `SimConfig`, any active boosts and slows
     fn speed(&self) -> f32 {
-        self.stats.speed*MOVEMENT_SPEED
+        let boost: f32 = self.active_effects().map(|effect| match *effect {
+            AbilityEffect::Boost{speed, ..} => speed,
+            _ => 1.0,
+        }).product();
+        let slow: f32 = self.statuses.iter().map(|status| match status.effect.kind {
+            StatusKind::Slow{factor} => factor.powi(status.stacks as i32),
+            _ => 1.0,
+        }).product();
+        self.stats.speed*SimConfig::current().movement_speed*boost*slow
+    }
+
+    /// Returns the distance the `GameChar` can move during a single tick
+    fn step(&self) -> f32 {
+        self.speed()*10.0/TICKS_PER_SECOND as f32
     }
 
-    /// Moves self forward on the x-scale, away from its own base
-    fn move_forward(&mut self, ctx: &mut Context){
+    /// Moves self forward on the x-scale, away from its own base, stopping at the end of the lane
+    fn move_forward(&mut self){
+        self.move_towards(1.0);
+    }
+
+    /// Moves self towards `target`, measured like `advance()`, without overshooting it
+    fn move_towards(&mut self, target: f32){
+        let step = self.step().min((target - self.advance()).abs());
+        let step = if target > self.advance() {step} else {-step};
         match self.side{
-            Side::Left => self.position += self.speed()*(ggez::timer::get_delta(ctx).subsec_nanos() as f32/1e8),
-            Side::Right => self.position -= self.speed()*(ggez::timer::get_delta(ctx).subsec_nanos() as f32/1e8),
+            Side::Left => self.position += step,
+            Side::Right => self.position -= step,
         }
     }
 
-    /// Makes &mut self attack as many `GameChar`s in enemies as possible,
-    /// until it has reached the maximum number of targets
-    /// If there was no target to attack, it will move by calling self.move_forward()
-    pub fn attack_move(&mut self, ctx: &mut Context, enemies: &mut Vec<GameChar>, base: &mut Base){
+    /// Reduces the hp of self by `damage`, lessened by its armor.
+    /// Each hit deals at least MIN_DAMAGE, no matter how high the armor
+    pub fn take_damage(&mut self, damage: f32){
+        let scale = SimConfig::current().damage_scale;
+        let damage = (damage - self.armor()*scale).max(MIN_DAMAGE*scale);
+        self.stats.hp = self.stats.hp.zero_saturating_sub(damage);
+    }
+
+    /// Makes &mut self attack as many `GameChar`s in enemies on its lane as possible,
+    /// until it has reached the maximum number of targets, followed by neutral objectives and enemy bases
+    /// If there was no target to attack, it will move according to its `Order`
+    pub fn attack_move(&mut self, enemies: &mut [&mut GameChar], bases: &mut [&mut Base], objectives: &mut [Objective], dice: &mut Dice){
+        //cocoons, burrowed and stunned units neither attack nor move
+        if self.morphing.is_some() || self.is_burrowed() || self.is_stunned(){
+            return;
+        }
+        //structures and deployed units hold their ground, whatever the order
+        if self.structure || self.is_deployed(){
+            self.attack(enemies, bases, objectives, dice);
+            return;
+        }
+
+        match self.order{
+            Order::Advance => if !self.attack(enemies, bases, objectives, dice) {
+                self.move_forward();
+            },
+            Order::Hold(target) => if !self.attack(enemies, bases, objectives, dice) {
+                self.move_towards(target);
+            },
+            //retreating units only fight back once they are home
+            Order::Retreat => if self.advance() > 0.0 {
+                self.move_towards(0.0);
+            } else {
+                self.attack(enemies, bases, objectives, dice);
+            },
+        }
+    }
 
+    /// Inflicts the on-hit status effects of self and those of active abilities on `target`
+    fn inflict_statuses(&self, target: &mut GameChar){
+        for effect in &self.on_hit {
+            target.apply_status(effect);
+        }
+        for effect in self.active_effects() {
+            if let AbilityEffect::Inflict{ref status} = *effect {
+                target.apply_status(status);
+            }
+        }
+    }
+
+    /// Makes &mut self attack as many targets in range as possible, missing some of them by chance.
+    /// Returns true if there has been an attack, hit or miss
+    fn attack(&mut self, enemies: &mut [&mut GameChar], bases: &mut [&mut Base], objectives: &mut [Objective], dice: &mut Dice) -> bool{
         //mobile means the opposite of immobile
         //mobile is truue when there has been no attack        
         let mut mobile = true;
         let mut attack_count = 0;
-        for enemy_unit in enemies {
+        for enemy_unit in enemies.iter_mut() {
             //ensure unit attacks only one target
             if attack_count == self.stats.targets{
                 break;
             }
-            //determine if target is in range
-            if self.in_range(enemy_unit) {
+            //determine if target is in range, burrowed units and units on other lanes can't be targeted
+            if enemy_unit.lane == self.lane && self.in_range(&**enemy_unit) && !enemy_unit.is_burrowed() {
                 attack_count += 1;    
                 mobile = false;
-                enemy_unit.stats.hp = enemy_unit.stats.hp.zero_saturating_sub(self.damage());
+                if !dice.misses() {
+                    enemy_unit.take_damage(dice.damage(self.damage()));
+                    self.inflict_statuses(enemy_unit);
+                }
             }
         }
-        if attack_count < self.stats.targets && self.in_range(base) {
-            mobile = false;
-            base.hp = base.hp.zero_saturating_sub(self.damage());
+        for objective in objectives {
+            if attack_count == self.stats.targets{
+                break;
+            }
+            if objective.lane == self.lane && objective.is_attackable() && self.in_range(objective) {
+                attack_count += 1;
+                mobile = false;
+                if !dice.misses() {
+                    objective.take_damage(dice.damage(self.damage()), self.side);
+                }
+            }
         }
-        //move, if possible
-        if mobile{
-            self.move_forward(ctx);
+        for base in bases.iter_mut() {
+            if attack_count == self.stats.targets{
+                break;
+            }
+            //destroyed bases are no longer attacked
+            let in_range = match base.position_on(self.lane) {
+                Some(position) => (self.position() - position).abs() < self.range(),
+                None => false,
+            };
+            if in_range && base.hp > 0.0 {
+                attack_count += 1;
+                mobile = false;
+                if !dice.misses() {
+                    base.hp = base.hp.zero_saturating_sub(dice.damage(self.damage()));
+                }
+            }
         }
+        !mobile
     }
 }
 
 
 
-/// The base each player has to defend
-#[derive(Debug, Clone)]
+/// The base each player has to defend. It borders on one or more lanes
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Base{
-    pub sprite: graphics::Image,
+    /// Path of the sprite in the ggez filesystem
+    pub sprite: String,
     pub hp: f32,
-    position: f32,
+    pub ends: Vec<LaneEnd>,
 }
 impl Base {
-    ///Returns a new `Base` on the specified side of the map
-    pub fn new(ctx: &mut Context, side: Side) -> GameResult<Base> {
-        let sprite = graphics::Image::new(ctx, "/hatchery.png")?;
-        let mut position = 1.0; //All the way to the right
-        if side == Side::Left { 
-            position = 0.0;     //All the way to the left
-        }
-        Ok(Base{
-            sprite: sprite,
-            hp: 100.0,
-            position: position,
-        })
+    ///Returns a new `Base` as described by the passed definition, at the specified lane ends
+    pub fn new(def: &BaseDef, ends: &[LaneEnd]) -> Base {
+        Base{
+            sprite: def.sprite.clone(),
+            hp: def.hp,
+            ends: ends.to_vec(),
+        }
     }
-}
-impl Position for Base {
-    /// Returns the (x-Axis) position of the `GameChar`, multiplicated with
-    /// the MAP_SCALE constant
-    fn position(&self) -> f32 {
-        self.position * MAP_SCALE
+
+    /// Returns the (x-Axis) position of the `Base` on the passed lane, multiplicated with
+    /// the map scale of the `SimConfig`, or `None` if it doesn't border on the lane
+    pub fn position_on(&self, lane: usize) -> Option<f32> {
+        self.ends.iter().find(|end| end.lane == lane).map(|end| match end.side {
+            Side::Left => 0.0,                  //All the way to the left
+            Side::Right => SimConfig::current().map_scale,  //All the way to the right
+        })
     }
 }
 
@@ -209,9 +511,9 @@ impl Position for Base {
 
 impl Position for GameChar { 
     /// Returns the (x-Axis) position of the `GameChar`, multiplicated with
-    /// the MAP_SCALE constant
+    /// the map scale of the `SimConfig`
     fn position(&self) -> f32 {
-        self.position * MAP_SCALE
+        self.position * SimConfig::current().map_scale
     }
 }
 
@@ -227,4 +529,35 @@ impl ZeroSaturationOps for f32 {
         let result = self - rhs;
         if result<0.0 {0.0} else {result}
     }
-}
\ No newline at end of file
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use data::GameData;
+    use status::Stacking;
+
+    /// Returns the hp a zergling loses to a second of acid with the passed damage scale
+    fn acid_damage(damage_scale: f32) -> f32 {
+        SimConfig{damage_scale: damage_scale, ..SimConfig::default()}.apply();
+        let data = GameData::bundled();
+        let def = data.faction("zerg").unwrap().units.iter().find(|def| def.kind == UnitKind::Ling).unwrap();
+        let mut ling = GameChar::new(def, LaneEnd{lane: 0, side: Side::Left});
+        ling.apply_status(&StatusEffect{
+            name: String::from("Acid"),
+            kind: StatusKind::Acid{damage: 1.0},
+            duration: 3.0,
+            stacking: Stacking::Refresh,
+            max_stacks: 1,
+        });
+        let hp = ling.stats.hp;
+        ling.update_statuses(1.0);
+        SimConfig::default().apply();
+        hp - ling.stats.hp
+    }
+
+    #[test]
+    fn acid_scales_with_the_damage_scale() {
+        assert!((acid_damage(DAMAGE_SCALE) - 1.0).abs() < 1e-4);
+        assert!((acid_damage(2.0*DAMAGE_SCALE) - 2.0).abs() < 1e-4);
+    }
+}
@@ -3,19 +3,48 @@ use ggez::{Context, GameResult};
 use ggez::graphics;
 use ggez::event;
 use std::time;
+use std::collections::HashMap;
 use ggez;
 
-use player::Player;
-use gameobject::{GameChar};
-use constants::SPEED;
+use player::{Player, Command, Controltype};
+use gameobject::{GameChar, Order};
+use status::StatusKind;
+use data::GameData;
+use objective::ObjectiveKind;
+use setup::Setup;
+use save::SaveGame;
+use scenario::Scenario;
+use simulation::Simulation;
+use bot::Bot;
+use lockstep::Lockstep;
+use spectator::{Broadcast, Spectator};
+use constants::{SPEED, MAP_SCALE, TICKS_PER_SECOND};
 use traits::Position;
 
 /// Tracks the global game state
 pub struct GameState {
-    players: Vec<Player>,
+    sim: Simulation,
     offset: f32,
     last_start: time::Duration,
     font: graphics::Font,
+    /// Sprites of units and bases, by path. Sprites are loaded the first time they are drawn
+    sprites: HashMap<String, graphics::Image>,
+    /// The connection to the other player of a networked match
+    net: Option<Lockstep>,
+    /// Whether the match is only watched: keys move the camera instead of issuing commands
+    observer: bool,
+    /// The part of the map observers look at
+    camera: Camera,
+    /// The whole screen, the map being seen through the camera
+    screen: graphics::Rect,
+    /// Army and production values of all players, sampled every second while observing
+    graphs: Vec<Sample>,
+    /// Sends the match to spectators
+    broadcast: Option<Broadcast>,
+    /// Receives the watched match if it is played by another instance
+    spectator: Option<Spectator>,
+    /// Whether the decisions of behavior tree AIs are drawn, toggled with F3
+    trees: bool,
 
     //only for quick debugging, consider removing in final release
     debug: bool,
@@ -23,80 +52,216 @@ pub struct GameState {
 }
 
 impl GameState {
-    /// Returns a new GameState struct
-    pub fn new(ctx: &mut Context) -> GameResult<GameState>{
+    /// Returns a new GameState struct for a match with the passed setup, whose randomness derives from `seed`
+    pub fn new(ctx: &mut Context, setup: &Setup, seed: u64) -> GameResult<GameState>{
+        let data = GameData::load(ctx, "/gamedata.json")?;
+        let sim = Simulation::new(setup, &data, seed)?;
+        GameState::from_save(ctx, sim.save())
+    }
+
+    /// Returns a new GameState struct that plays the passed scenario
+    pub fn with_scenario(ctx: &mut Context, scenario: &Scenario, seed: u64) -> GameResult<GameState>{
+        let data = GameData::load(ctx, "/gamedata.json")?;
+        GameState::from_save(ctx, scenario.start(&data, seed)?)
+    }
+
+    /// Returns the seed the randomness of the match derives from
+    pub fn seed(&self) -> u64{
+        self.sim.dice.seed()
+    }
+
+    /// Returns whether the scenario has been passed, or `None` if no scenario has been decided
+    pub fn scenario_passed(&self) -> Option<bool>{
+        self.sim.passed
+    }
+
+    /// Hands the player at the passed index to a bot that has already connected
+    pub fn attach_bot(&mut self, player: usize, bot: Bot){
+        self.sim.attach(player, bot);
+    }
+
+    /// Plays the match against the other player of the passed connection, see `Lockstep`
+    pub fn connect(&mut self, net: Lockstep){
+        self.net = Some(net);
+    }
+
+    /// Only watches the match, see `observer`
+    pub fn observe(&mut self){
+        self.observer = true;
+    }
+
+    /// Sends the match to spectators connecting to the passed broadcast
+    pub fn broadcast(&mut self, broadcast: Broadcast){
+        self.broadcast = Some(broadcast);
+    }
+
+    /// Returns a GameState struct that watches the match broadcast to the passed spectator, starting at the passed snapshot
+    pub fn spectate(ctx: &mut Context, spectator: Spectator, save: SaveGame) -> GameResult<GameState>{
+        let mut state = GameState::from_save(ctx, save)?;
+        state.observer = true;
+        state.spectator = Some(spectator);
+        Ok(state)
+    }
+
+    /// Returns a GameState struct that resumes the passed saved match
+    pub fn from_save(ctx: &mut Context, save: SaveGame) -> GameResult<GameState>{
         let font = graphics::Font::new(ctx, "/Roboto-Regular.ttf", 16)?;
+        //make room for the additional lanes below the first one
+        let height = 600.0 + LANE_SPACING*(save.lanes - 1) as f32;
+        let screen = graphics::Rect::new(0.0, 0.0, 800.0, height);
+        graphics::set_screen_coordinates(ctx, screen)?;
         Ok(GameState{
-            players: vec!(Player::new(ctx, Side::Left)?, Player::new(ctx, Side::Right)?),
+            sim: Simulation::from_save(save),
             offset: 0.0,
             last_start: time::Duration::from_secs(0),
             font: font,
+            sprites: HashMap::new(),
+            net: None,
+            observer: false,
+            camera: Camera::default(),
+            screen: screen,
+            graphs: Vec::new(),
+            broadcast: None,
+            spectator: None,
+            trees: false,
             debug: true,
             debug_once: true,
         })
     }
+
+    /// Loads the sprites of all units and bases that haven't been drawn before
+    fn load_sprites(&mut self, ctx: &mut Context) -> GameResult<()>{
+        for player in &self.sim.players {
+            let paths = Some(&player.base.sprite).into_iter().chain(player.units.iter().map(|unit| &unit.sprite));
+            for path in paths {
+                if !self.sprites.contains_key(path) {
+                    let sprite = graphics::Image::new(ctx, path)?;
+                    self.sprites.insert(path.clone(), sprite);
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Samples the army and production values of all players once every second of the match
+    fn sample(&mut self){
+        // Loading an earlier save restarts the graphs
+        if self.graphs.last().map(|sample| sample.tick > self.sim.tick).unwrap_or(false) {
+            self.graphs.clear();
+        }
+        let due = self.graphs.last().map(|sample| self.sim.tick >= sample.tick + TICKS_PER_SECOND as u64).unwrap_or(true);
+        if due {
+            self.graphs.push(Sample{
+                tick: self.sim.tick,
+                values: self.sim.players.iter().map(|player| (player.army_value(), player.production_value())).collect(),
+            });
+        }
+    }
+
+    /// Draws a line graph of one of the sampled values of every player into the passed area of the screen
+    fn draw_graph(&self, ctx: &mut Context, title: &str, area: graphics::Rect, value: fn(&(u32, u32)) -> u32) -> GameResult<()>{
+        graphics::set_color(ctx, graphics::WHITE)?;
+        graphics::rectangle(ctx, graphics::DrawMode::Line(1.0), area)?;
+        let max = self.graphs.iter().flat_map(|sample| sample.values.iter().map(value)).max().unwrap_or(0).max(1);
+        let text = graphics::Text::new(ctx, &format!("{} (up to {})", title, max), &self.font)?;
+        graphics::draw(ctx, &text, graphics::Point2::new(area.x + 4.0, area.y + 2.0), 0.0)?;
+        if self.graphs.len() < 2 {
+            return Ok(());
+        }
+        for index in 0..self.sim.players.len() {
+            let points: Vec<graphics::Point2> = self.graphs.iter().enumerate().map(|(i, sample)| {
+                let v = sample.values.get(index).map(value).unwrap_or(0);
+                graphics::Point2::new(
+                    area.x + area.w*i as f32/(self.graphs.len() - 1) as f32,
+                    area.y + area.h - area.h*v as f32/max as f32,
+                )
+            }).collect();
+            graphics::set_color(ctx, player_color(index))?;
+            graphics::line(ctx, &points, 2.0)?;
+        }
+        graphics::set_color(ctx, graphics::WHITE)
+    }
+
+    /// Records the result of the match once it is over
+    fn finish(&self) -> GameResult<()>{
+        let result = self.sim.result();
+        println!("Match over: {:?}", result);
+        if let (Some(scenario), Some(passed)) = (self.sim.scenario.as_ref(), self.sim.passed) {
+            println!("Scenario {}: {}", scenario.name, if passed {"passed"} else {"failed"});
+        }
+        result.append("results.jsonl")
+    }
 }
 
 impl event::EventHandler for GameState {
     /// Handles key press events
     fn key_down_event(&mut self, ctx: &mut Context, keycode: Keycode, _keymod: Mod, _repeat: bool){
-        match keycode{
-            // Spawn a zergling for Player 0
-            Keycode::Num1 => {
-                if self.players[0].minerals>200{
-                    self.players[0].minerals -= 200;
-                    self.players[0].units.push(GameChar::ling(ctx, Side::Left).unwrap());
-
-                }
-            }
-            // Spawn a hydra for Player 0
-            Keycode::Num2 => {
-                if self.players[0].minerals>300{
-                    self.players[0].minerals -= 300;
-                    self.players[0].units.push(GameChar::hydra(ctx, Side::Left).unwrap());
+        // Only changes what is drawn, so players and observers alike may look into the AIs
+        if keycode == Keycode::F3 {
+            self.trees = !self.trees;
+            return;
+        }
+        if self.observer {
+            self.camera.control(keycode);
+            // Observers may still save the match
+            if keycode == Keycode::F5 {
+                match self.sim.save().write(QUICKSAVE) {
+                    Ok(()) => println!("Saved to {}", QUICKSAVE),
+                    Err(e) => println!("Could not save: {}", e),
                 }
             }
-            // Spawn a baneling for Player 0
-            Keycode::Num3 => {
-                if self.players[0].minerals>400{
-                    self.players[0].minerals -= 400;
-                    self.players[0].units.push(GameChar::bane(ctx, Side::Left).unwrap());
-                    
-                }
+            return;
+        }
+        match keycode{
+            // Both players simulate the whole match, changing it locally would make them diverge
+            Keycode::Backspace | Keycode::F9 if self.net.is_some() => {
+                println!("Not available in networked matches");
             }
- 
-            // Spawn a zergling for Player 1
-            Keycode::Kp1 => {
-                if self.players[1].minerals>200{
-                    self.players[1].minerals -= 200;
-                    self.players[1].units.push(GameChar::ling(ctx, Side::Right).unwrap());
 
+            // Replace the unit vector with a new, empty one, effectively removing all units
+            Keycode::Backspace => {
+                for player in &mut self.sim.players{
+                    player.units = Vec::with_capacity(50);
                 }
             }
-            // Spawn a hydra for Player 1
-            Keycode::Kp2 => {
-                if self.players[1].minerals>300{
-                    self.players[1].minerals -= 300;
-                    self.players[1].units.push(GameChar::hydra(ctx, Side::Right).unwrap());
+
+            // Quicksave and quickload the whole match
+            Keycode::F5 => {
+                match self.sim.save().write(QUICKSAVE) {
+                    Ok(()) => println!("Saved to {}", QUICKSAVE),
+                    Err(e) => println!("Could not save: {}", e),
                 }
             }
-            // Spawn a baneling for Player 1
-            Keycode::Kp3 => {
-                if self.players[1].minerals>400{
-                    self.players[1].minerals -= 400;
-                    self.players[1].units.push(GameChar::bane(ctx, Side::Right).unwrap());
-                    
+            Keycode::F9 => {
+                match SaveGame::read(QUICKSAVE).and_then(|save| GameState::from_save(ctx, save)) {
+                    Ok(mut state) => {
+                        // Bots that claimed their players over TCP can't be started again, they carry over
+                        state.sim.adopt(&mut self.sim);
+                        state.broadcast = self.broadcast.take();
+                        *self = state;
+                    }
+                    Err(e) => println!("Could not load {}: {}", QUICKSAVE, e),
                 }
             }
 
-            // Replace the unit vector with a new, empty one, effectively removing all units
-            Keycode::Backspace => {
-                for player in &mut self.players{
-                    player.units = Vec::with_capacity(50);
+            _ => {
+                let command = {
+                    let humans: Vec<&Player> = match self.net{
+                        Some(ref net) => {
+                            let local = &self.sim.players[self.sim.humans[net.local]];
+                            vec!(local, local)
+                        }
+                        None => self.sim.humans.iter().map(|&i| &self.sim.players[i]).collect(),
+                    };
+                    key_command(keycode, &humans)
+                };
+                match (command, self.net.as_mut()){
+                    // Either set of keys controls the local player of a networked match
+                    (Some((_, command)), Some(net)) => net.command(command),
+                    (Some((human, command)), None) => self.sim.command(human, command),
+                    (None, _) => {}
                 }
             }
-
-            _ => (),  // Unknown key, do nothing
         }
        
     }
@@ -114,37 +279,35 @@ impl event::EventHandler for GameState {
 
 
 
-        // Deal damage
-        // The following implementation seems to favour the player whose units attack first
-        // But as units with 0 hp are only sorted out at the end of the tick, this is not an issue
-        // (effectively allowing units whose hp were reduced to 0 or bellows to attack 1 more time)
-        {
-            let (player_0, player_1) = &mut self.players[..].split_at_mut(1);
-
-            // Left player's units deal damage and move
-            for unit in &mut player_0[0].units {
-                unit.attack_move(ctx, &mut player_1[0].units, &mut player_1[0].base);
-            }
-
-            // Right player's units deal damage and move
-            for unit in &mut player_1[0].units{
-                unit.attack_move(ctx, &mut player_0[0].units, &mut player_0[0].base);
+        // Spectators show the newest snapshot of the match instead of simulating it
+        if let Some(ref mut spectator) = self.spectator{
+            if let Some(sim) = spectator.latest()? {
+                self.sim = sim;
             }
         }
-        
-        //this could potentially be adapted to be multithreaded
-        for player in &mut self.players{
-            //Gain resources
-            player.minerals += 1;
-
-            //remove dead units
-            let mut living_units = Vec::with_capacity(player.units.capacity());
-            for unit in &player.units{
-                if unit.stats.hp > 0.0{
-                    living_units.push(unit.clone());
-                }
+        // Advance the simulation in fixed ticks, no matter the frame rate
+        while self.spectator.is_none() && ggez::timer::check_update_time(ctx, TICKS_PER_SECOND){
+            let over = self.sim.over;
+            match self.net{
+                // Wait for the other player's commands, catching up once they arrive
+                Some(ref mut net) if !over => if !net.tick(&mut self.sim)? {
+                    break;
+                },
+                _ => self.sim.tick()?,
+            }
+            if self.sim.over && !over{
+                self.finish()?;
             }
-            player.units = living_units;
+        }
+        if let Some(ref mut broadcast) = self.broadcast{
+            broadcast.update(&self.sim)?;
+        }
+        if self.observer{
+            self.sample();
+        }
+        // Scenarios are run to test a situation, there's nothing left to see once they are decided
+        if self.sim.passed.is_some(){
+            ctx.quit()?;
         }
         Ok(())
     }
@@ -152,47 +315,165 @@ impl event::EventHandler for GameState {
     /// Draws gameplay elements, such as `GameChar`s
     fn draw(&mut self, ctx: &mut Context) -> GameResult<()> {
         graphics::clear(ctx);
+        self.load_sprites(ctx)?;
 
         if self.debug_once && self.debug{
             self.debug_once = false;
-            println!("{:?}", self.players);
+            println!("{:?}", self.sim.players);
         }
-        for player in & self.players {
+        //the map is seen through the camera, which only observers can move
+        graphics::set_screen_coordinates(ctx, self.camera.view(self.screen))?;
+        //draw the neutral objectives of each lane and who holds them
+        for objective in &self.sim.objectives {
+            let owner = match objective.owner {
+                Some(Side::Left) => "left",
+                Some(Side::Right) => "right",
+                None => "nobody",
+            };
+            let s = match objective.def.kind {
+                ObjectiveKind::Minerals{..} => format!("{} (held by {})", objective.def.name, owner),
+                ObjectiveKind::Camp{..} if objective.hp > 0.0 => format!("{}: {:.0} hp", objective.def.name, objective.hp),
+                ObjectiveKind::Camp{..} => format!("{} respawns in {:.0}s", objective.def.name, objective.respawn),
+            };
+            let text = graphics::Text::new(ctx, s.as_str(), &self.font)?;
+            let dest_point = graphics::Point2::new(objective.position(), lane_y(objective.lane) - 40.0);
+            graphics::draw(ctx, &text, dest_point, 0.0)?;
+        }
+
+        for (index, player) in self.sim.players.iter().enumerate() {
+            let color = player_color(index);
+
+            //draw each players' base at every lane end it borders on, faded out once destroyed
+            for end in &player.base.ends {
+                let tint = if player.is_alive() {graphics::WHITE} else {graphics::Color::new(1.0, 1.0, 1.0, 0.3)};
+                let tint = faction_tint(tint, player.tint);
+                let p = graphics::DrawParam {
+                    dest: graphics::Point2::new(player.base.position_on(end.lane).unwrap_or(0.0), lane_y(end.lane) - 200.0),
+                    scale: graphics::Point2::new(0.25, 0.25),
+                    rotation: 0.0,
+                    color: Some(tint),
+                    ..Default::default()
+                };
+
+                graphics::draw_ex(ctx, &self.sprites[&player.base.sprite], p)?;
+            }
+
+            graphics::set_color(ctx, color)?;
+
+            //mark each unit with its player's color
+            for unit in &player.units{
+                let marker = graphics::Rect::new(unit.position(), lane_y(unit.lane()) - 6.0, 20.0, 4.0);
+                graphics::rectangle(ctx, graphics::DrawMode::Fill, marker)?;
+            }
+            graphics::set_color(ctx, graphics::WHITE)?;
 
-            //draw each players' base!
-            let p = graphics::DrawParam {
-                dest: graphics::Point2::new(player.base.position(), 200.0),
-                scale: graphics::Point2::new(0.25, 0.25),
+            //draw all units!
+            for unit in &player.units{
+
+                let color = match (unit_color(unit), player.tint) {
+                    (None, None) => None,
+                    (color, tint) => Some(faction_tint(color.unwrap_or(graphics::WHITE), tint)),
+                };
+                let p = graphics::DrawParam {
+                dest: graphics::Point2::new(unit.position(), lane_y(unit.lane())),
+                scale: graphics::Point2::new(0.15, 0.15),
                 rotation: 0.0,
+                color: color,
                 ..Default::default()
-            };
+                };
 
-            graphics::draw_ex(ctx, &player.base.sprite, p)?;
+                graphics::draw_ex(ctx, &self.sprites[&unit.sprite], p)?;
+
+            }
+
+        }
+
+        //the HUD stays in place, whatever the camera shows
+        graphics::set_screen_coordinates(ctx, self.screen)?;
+        let columns = self.sim.players.len();
+        for (index, player) in self.sim.players.iter().enumerate() {
+            //each player's HUD gets its own column, in the player's color
+            let x = if columns > 1 {MAP_SCALE*index as f32/(columns - 1) as f32} else {0.0};
+            graphics::set_color(ctx, player_color(index))?;
+
+            //draw each player's team and base hp
+            {
+                let s = if player.is_alive() {
+                    format!("Player {} ({}, Team {}): {:.0} hp", index + 1, player.faction, player.team + 1, player.base.hp)
+                } else {
+                    format!("Player {} ({}, Team {}): defeated", index + 1, player.faction, player.team + 1)
+                };
+                let text = graphics::Text::new(ctx, s.as_str(), &self.font)?;
+                graphics::draw(ctx, &text, graphics::Point2::new(x, 60.0), 0.0)?;
+            }
 
              //draw each player's minerals
             {
                
                 let s = format!("Minerals: {}", player.minerals);
                 let text = graphics::Text::new(ctx, s.as_str(), &self.font)?;
-                let dest_point = graphics::Point2::new(player.base.position(), 100.0);
+                let dest_point = graphics::Point2::new(x, 100.0);
                 graphics::draw(ctx, &text, dest_point, 0.0)?;
             }
-            
 
-            //draw all units!
-            for unit in &player.units{
-                
-                let p = graphics::DrawParam {
-                dest: graphics::Point2::new(unit.position(), 400.0),
-                scale: graphics::Point2::new(0.15, 0.15),
-                rotation: 0.0,
-                ..Default::default()
+            //draw each player's army order and rally point
+            {
+                let order = match player.order {
+                    Order::Advance => String::from("Advance"),
+                    Order::Hold(position) => format!("Hold at {:.0}%", position*100.0),
+                    Order::Retreat => String::from("Retreat"),
+                };
+                let rally = match player.rally {
+                    Some(position) => format!("{:.0}%", position*100.0),
+                    None => String::from("none"),
                 };
+                let s = format!("Orders: {}, Rally: {}", order, rally);
+                let text = graphics::Text::new(ctx, s.as_str(), &self.font)?;
+                let dest_point = graphics::Point2::new(x, 80.0);
+                graphics::draw(ctx, &text, dest_point, 0.0)?;
+            }
 
-                graphics::draw_ex(ctx, &unit.sprite, p)?;
+            //draw each player's upgrade levels
+            for (i, research) in player.research.iter().enumerate(){
+                let s = format!("{}: {}/{}", research.upgrade.name, research.level, research.upgrade.max_level);
+                let text = graphics::Text::new(ctx, s.as_str(), &self.font)?;
+                let dest_point = graphics::Point2::new(x, 120.0 + 16.0*i as f32);
+                graphics::draw(ctx, &text, dest_point, 0.0)?;
+            }
 
+            //draw the nodes each behavior tree evaluates, the path to its decisions in the player's color
+            match player.controltype {
+                Controltype::BehaviorTree(_) if self.trees && player.is_alive() => {
+                    let top = 130.0 + 16.0*player.research.len() as f32;
+                    for (i, node) in self.sim.trace(index).iter().enumerate() {
+                        let color = if node.success {player_color(index)} else {graphics::Color::new(0.5, 0.5, 0.5, 1.0)};
+                        graphics::set_color(ctx, color)?;
+                        let text = graphics::Text::new(ctx, node.label.as_str(), &self.font)?;
+                        let dest_point = graphics::Point2::new(x + 12.0*node.depth as f32, top + 16.0*i as f32);
+                        graphics::draw(ctx, &text, dest_point, 0.0)?;
+                    }
+                }
+                _ => (),
             }
+        }
+        graphics::set_color(ctx, graphics::WHITE)?;
+
+        //observers follow how the armies and economies of all players develop
+        if self.observer {
+            let (w, h) = (self.screen.w/2.0 - 20.0, 80.0);
+            let y = self.screen.h - h - 10.0;
+            self.draw_graph(ctx, "Army value", graphics::Rect::new(10.0, y, w, h), |&(army, _)| army)?;
+            self.draw_graph(ctx, "Production", graphics::Rect::new(self.screen.w/2.0 + 10.0, y, w, h), |&(_, production)| production)?;
+        }
 
+        //announce the end of the match
+        if self.sim.over {
+            let s = match self.sim.winner {
+                Some(team) => format!("Team {} wins!", team + 1),
+                None => String::from("Draw!"),
+            };
+            let text = graphics::Text::new(ctx, s.as_str(), &self.font)?;
+            graphics::draw(ctx, &text, graphics::Point2::new(MAP_SCALE/2.0, 20.0), 0.0)?;
         }
 
         graphics::present(ctx);
@@ -202,8 +483,187 @@ impl event::EventHandler for GameState {
 
 
 /// Used to identify the two sides of the battlefield, Left and Right
-#[derive(Debug, Clone, Copy, PartialEq)]
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
 pub enum Side{
     Left,
     Right,
-}
\ No newline at end of file
+}
+
+
+/// The army and production values of every player at a tick, drawn as graphs for observers
+#[derive(Debug, Clone)]
+struct Sample{
+    tick: u64,
+    values: Vec<(u32, u32)>,
+}
+
+/// The part of the map observers look at, moved with the arrow keys and zoomed with `=` and `-`
+#[derive(Debug, Clone, Copy)]
+struct Camera{
+    /// Offset of the center of the view from the center of the screen
+    x: f32,
+    y: f32,
+    zoom: f32,
+}
+
+impl Default for Camera {
+    fn default() -> Camera {
+        Camera{x: 0.0, y: 0.0, zoom: 1.0}
+    }
+}
+
+impl Camera {
+    /// Moves or zooms the camera if the passed key does so, `R` resets it
+    fn control(&mut self, keycode: Keycode){
+        let step = CAMERA_STEP/self.zoom;
+        match keycode {
+            Keycode::Left => self.x -= step,
+            Keycode::Right => self.x += step,
+            Keycode::Up => self.y -= step,
+            Keycode::Down => self.y += step,
+            Keycode::Equals => self.zoom = (self.zoom*1.25).min(4.0),
+            Keycode::Minus => self.zoom = (self.zoom/1.25).max(0.25),
+            Keycode::R => *self = Camera::default(),
+            _ => {}
+        }
+    }
+
+    /// Returns the part of the passed screen that is in view
+    fn view(&self, screen: graphics::Rect) -> graphics::Rect {
+        let (w, h) = (screen.w/self.zoom, screen.h/self.zoom);
+        graphics::Rect::new(screen.x + self.x + (screen.w - w)/2.0, screen.y + self.y + (screen.h - h)/2.0, w, h)
+    }
+}
+
+/// Distance the camera moves per key press at normal zoom
+const CAMERA_STEP: f32 = 50.0;
+
+/// File the match is quicksaved to
+const QUICKSAVE: &str = "quicksave.json";
+
+/// Vertical distance between two lanes on screen
+const LANE_SPACING: f32 = 250.0;
+
+/// Returns the y-coordinate units on the passed lane are drawn at
+fn lane_y(lane: usize) -> f32 {
+    400.0 + LANE_SPACING*lane as f32
+}
+
+/// Returns the color identifying the player at the passed index
+fn player_color(index: usize) -> graphics::Color {
+    let colors = [
+        graphics::Color::new(0.4, 0.6, 1.0, 1.0),
+        graphics::Color::new(1.0, 0.4, 0.4, 1.0),
+        graphics::Color::new(0.4, 1.0, 0.5, 1.0),
+        graphics::Color::new(1.0, 0.9, 0.3, 1.0),
+        graphics::Color::new(0.9, 0.5, 1.0, 1.0),
+        graphics::Color::new(0.3, 1.0, 1.0, 1.0),
+    ];
+    colors[index % colors.len()]
+}
+
+/// Returns the tint a unit is drawn with, reflecting its state.
+/// Morphing units are drawn faded out, like a cocoon, burrowed units darkened and deployed units grey.
+/// Otherwise the most recent status effect determines the tint, and boosted units are tinted red
+fn unit_color(unit: &GameChar) -> Option<graphics::Color>{
+    if unit.morphing.is_some() {
+        return Some(graphics::Color::new(1.0, 1.0, 1.0, 0.4));
+    }
+    if unit.is_burrowed() {
+        return Some(graphics::Color::new(0.3, 0.3, 0.3, 0.8));
+    }
+    if unit.is_deployed() {
+        return Some(graphics::Color::new(0.7, 0.7, 0.8, 1.0));
+    }
+    if let Some(status) = unit.statuses.last() {
+        return Some(match status.effect.kind {
+            StatusKind::Slow{..} => graphics::Color::new(0.5, 0.6, 1.0, 1.0),
+            StatusKind::Stun => graphics::Color::new(1.0, 1.0, 0.3, 1.0),
+            StatusKind::Acid{..} => graphics::Color::new(0.5, 1.0, 0.4, 1.0),
+            StatusKind::Shred{..} => graphics::Color::new(1.0, 0.7, 0.3, 1.0),
+            StatusKind::Frenzy{..} => graphics::Color::new(0.8, 0.4, 1.0, 1.0),
+        });
+    }
+    if unit.is_boosted() {
+        return Some(graphics::Color::new(1.0, 0.6, 0.6, 1.0));
+    }
+    None
+}
+
+/// Returns the passed color tinted with the color of a faction, if it has one
+fn faction_tint(color: graphics::Color, tint: Option<[f32; 3]>) -> graphics::Color{
+    match tint {
+        Some([r, g, b]) => graphics::Color::new(color.r*r, color.g*g, color.b*b, color.a),
+        None => color,
+    }
+}
+
+/// Returns the index of the human player and the command bound to the pressed key, if there is any
+fn key_command(keycode: Keycode, players: &[&Player]) -> Option<(usize, Command)>{
+    let front = |i: usize| players.get(i).map(|player| player.front()).unwrap_or(0.0);
+    // Spawn keys refer to the n-th unit or structure in the player's roster
+    let spawn = |i: usize, n: usize, structure: bool| players.get(i)
+        .and_then(|player| player.roster.iter().filter(|def| def.structure == structure).nth(n))
+        .map(|def| (i, Command::Spawn(def.kind)));
+    let next_lane = |i: usize| players.get(i).map(|player| (player.lane + 1) % player.base.ends.len()).unwrap_or(0);
+    match keycode{
+        // Spawn units and structures for Player 0
+        Keycode::Num1 => spawn(0, 0, false),
+        Keycode::Num2 => spawn(0, 1, false),
+        Keycode::Num3 => spawn(0, 2, false),
+        Keycode::Num9 => spawn(0, 0, true),
+        Keycode::Num0 => spawn(0, 1, true),
+        // Research upgrades for Player 0, in the order of the data file
+        Keycode::Num4 => Some((0, Command::Research(0))),
+        Keycode::Num5 => Some((0, Command::Research(1))),
+        Keycode::Num6 => Some((0, Command::Research(2))),
+        Keycode::Num7 => Some((0, Command::Research(3))),
+        Keycode::Num8 => Some((0, Command::Research(4))),
+        // Morph Player 0's rearmost zergling into a baneling
+        Keycode::Q => Some((0, Command::Morph(0))),
+        // Army commands for Player 0
+        Keycode::A => Some((0, Command::Advance)),
+        Keycode::S => Some((0, Command::Hold(front(0)))),
+        Keycode::D => Some((0, Command::Retreat)),
+        Keycode::F => Some((0, Command::Rally(Some(front(0))))),
+        Keycode::G => Some((0, Command::Rally(None))),
+        // Abilities for Player 0, in the order they first appear in the data file
+        Keycode::Z => Some((0, Command::Ability(0))),
+        Keycode::X => Some((0, Command::Ability(1))),
+        Keycode::C => Some((0, Command::Ability(2))),
+        Keycode::V => Some((0, Command::Ability(3))),
+        // Send Player 0's new units down the next lane
+        Keycode::Tab => Some((0, Command::Lane(next_lane(0)))),
+
+        // Spawn units and structures for Player 1
+        Keycode::Kp1 => spawn(1, 0, false),
+        Keycode::Kp2 => spawn(1, 1, false),
+        Keycode::Kp3 => spawn(1, 2, false),
+        Keycode::Kp0 => spawn(1, 0, true),
+        Keycode::KpPeriod => spawn(1, 1, true),
+        // Research upgrades for Player 1, in the order of the data file
+        Keycode::Kp4 => Some((1, Command::Research(0))),
+        Keycode::Kp5 => Some((1, Command::Research(1))),
+        Keycode::Kp6 => Some((1, Command::Research(2))),
+        Keycode::Kp7 => Some((1, Command::Research(3))),
+        Keycode::Kp8 => Some((1, Command::Research(4))),
+        // Morph Player 1's rearmost zergling into a baneling
+        Keycode::Kp9 => Some((1, Command::Morph(0))),
+        // Army commands for Player 1
+        Keycode::KpPlus => Some((1, Command::Advance)),
+        Keycode::KpEnter => Some((1, Command::Hold(front(1)))),
+        Keycode::KpMinus => Some((1, Command::Retreat)),
+        Keycode::KpMultiply => Some((1, Command::Rally(Some(front(1))))),
+        Keycode::KpDivide => Some((1, Command::Rally(None))),
+        // Abilities for Player 1, in the order they first appear in the data file
+        Keycode::Insert => Some((1, Command::Ability(0))),
+        Keycode::Home => Some((1, Command::Ability(1))),
+        Keycode::PageUp => Some((1, Command::Ability(2))),
+        Keycode::PageDown => Some((1, Command::Ability(3))),
+        // Send Player 1's new units down the next lane
+        Keycode::End => Some((1, Command::Lane(next_lane(1)))),
+
+        _ => None,  // Unknown key, do nothing
+    }
+}
@@ -0,0 +1,273 @@
+use ggez::GameResult;
+
+use player::{Command, Controltype, AiParams};
+use gameobject::UnitKind;
+use data::GameData;
+use setup::Setup;
+use simulation::Simulation;
+use constants::TICKS_PER_SECOND;
+
+
+/// Every kind of unit, in the order they are counted in an `Observation`
+const KINDS: [UnitKind; 8] = [
+    UnitKind::Ling, UnitKind::Hydra, UnitKind::Bane, UnitKind::Spine,
+    UnitKind::Spore, UnitKind::Marine, UnitKind::Marauder, UnitKind::Tank,
+];
+
+/// Number of equally long stretches of the lane units are counted in, from the agent's base to the opponent's
+const BUCKETS: usize = 5;
+
+/// Index of the agent's player, it always plays the left side
+const AGENT: usize = 0;
+const OPPONENT: usize = 1;
+
+/// What the agent can do each step
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Action{
+    Noop,
+    Ling,
+    Hydra,
+    Bane,
+}
+
+impl Action {
+    /// Number of actions in the action space
+    pub const COUNT: usize = 4;
+
+    /// Returns the action with the passed index in the action space
+    pub fn from_index(index: usize) -> Option<Action> {
+        match index {
+            0 => Some(Action::Noop),
+            1 => Some(Action::Ling),
+            2 => Some(Action::Hydra),
+            3 => Some(Action::Bane),
+            _ => None,
+        }
+    }
+
+    /// Returns the kind of unit the action spawns, `None` for `Noop`
+    fn kind(&self) -> Option<UnitKind> {
+        match *self {
+            Action::Noop => None,
+            Action::Ling => Some(UnitKind::Ling),
+            Action::Hydra => Some(UnitKind::Hydra),
+            Action::Bane => Some(UnitKind::Bane),
+        }
+    }
+}
+
+/// The match as the agent sees it, flattened into numbers:
+/// its minerals, the hp of its base and of the opponent's, the number of units of each kind of `KINDS` of the agent
+/// and of the opponent, and the number of units of the agent and of the opponent in each stretch of the lane,
+/// starting at the agent's base
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Observation{
+    pub values: Vec<f32>,
+}
+
+impl Observation {
+    /// Number of values of every observation
+    pub const LEN: usize = 3 + 2*KINDS.len() + 2*BUCKETS;
+}
+
+/// Details of a step that aren't part of the observation
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Info{
+    /// Number of simulation ticks since the start of the episode
+    pub tick: u64,
+    /// Whether the action could be executed, spawning fails if the agent can't afford the unit
+    pub executed: bool,
+    /// The team that won, `None` while the match goes on or on a draw
+    pub winner: Option<usize>,
+    /// Whether the episode ended because it lasted too long, rather than with a destroyed base
+    pub timeout: bool,
+}
+
+/// A 1v1 match the agent plays as the left zerg player against an AI, advancing a fixed number of ticks per step.
+/// The reward of a step is the share of its starting hp the opponent's base lost minus the share the agent's base lost,
+/// plus 1.0 for winning and -1.0 for losing the match
+#[derive(Debug, Clone)]
+pub struct Env{
+    data: GameData,
+    /// Controls the opponent, the `SimpleAI` unless changed
+    pub opponent: Controltype,
+    /// Faction the opponent plays
+    pub opponent_faction: String,
+    /// Simulation ticks between two actions of the agent
+    pub ticks_per_step: u64,
+    /// Episodes that haven't been decided after this many seconds end as a draw
+    pub timeout: f32,
+    sim: Option<Simulation>,
+    /// Starting hp of the agent's and the opponent's base
+    base_hp: (f32, f32),
+}
+
+impl Env {
+    /// Returns a new environment with the passed game data. Call `reset` to start an episode
+    pub fn new(data: GameData) -> Env {
+        Env{
+            data: data,
+            opponent: Controltype::SimpleAI(AiParams::default()),
+            opponent_faction: String::from("zerg"),
+            ticks_per_step: 30,
+            timeout: 600.0,
+            sim: None,
+            base_hp: (1.0, 1.0),
+        }
+    }
+
+    /// Starts a new episode whose randomness derives from `seed`, returning the first observation
+    pub fn reset(&mut self, seed: u64) -> GameResult<Observation> {
+        let mut setup = Setup::one_vs_one(false, true);
+        setup.set_factions(&["zerg", &self.opponent_faction]);
+        let mut sim = Simulation::new(&setup, &self.data, seed)?;
+        sim.players[OPPONENT].controltype = self.opponent.clone();
+        self.base_hp = (sim.players[AGENT].base.hp.max(1.0), sim.players[OPPONENT].base.hp.max(1.0));
+        let observation = observe(&sim);
+        self.sim = Some(sim);
+        Ok(observation)
+    }
+
+    /// Executes the action and advances the match by `ticks_per_step` ticks.
+    /// Returns the next observation, the reward, whether the episode is done and details of the step.
+    /// Stepping a finished episode, or before the first `reset`, starts a new one with seed 0
+    pub fn step(&mut self, action: Action) -> GameResult<(Observation, f32, bool, Info)> {
+        let done = self.sim.as_ref().map(|sim| sim.over).unwrap_or(true);
+        if done {
+            self.reset(0)?;
+        }
+        let base_hp = self.base_hp;
+        let timeout = (self.timeout*TICKS_PER_SECOND as f32) as u64;
+        let sim = self.sim.as_mut().unwrap();
+        let before = (sim.players[AGENT].base.hp.max(0.0), sim.players[OPPONENT].base.hp.max(0.0));
+
+        let executed = match action.kind() {
+            Some(kind) => sim.players[AGENT].execute(Command::Spawn(kind), &mut sim.dice).is_ok(),
+            None => true,
+        };
+        for _ in 0..self.ticks_per_step {
+            if sim.over || sim.tick >= timeout {
+                break;
+            }
+            sim.tick()?;
+        }
+
+        let after = (sim.players[AGENT].base.hp.max(0.0), sim.players[OPPONENT].base.hp.max(0.0));
+        let mut reward = (before.1 - after.1)/base_hp.1 - (before.0 - after.0)/base_hp.0;
+        let team = sim.players[AGENT].team;
+        if sim.over {
+            reward += match sim.winner {
+                Some(winner) if winner == team => 1.0,
+                Some(_) => -1.0,
+                None => 0.0,
+            };
+        }
+        let timed_out = !sim.over && sim.tick >= timeout;
+        let info = Info{
+            tick: sim.tick,
+            executed: executed,
+            winner: if sim.over {sim.winner} else {None},
+            timeout: timed_out,
+        };
+        if timed_out {
+            sim.over = true;
+        }
+        Ok((observe(sim), reward, sim.over, info))
+    }
+}
+
+/// Returns the match as seen by the agent
+fn observe(sim: &Simulation) -> Observation {
+    let (agent, opponent) = (&sim.players[AGENT], &sim.players[OPPONENT]);
+    let mut values = Vec::with_capacity(Observation::LEN);
+    values.push(agent.minerals as f32);
+    values.push(agent.base.hp.max(0.0));
+    values.push(opponent.base.hp.max(0.0));
+    for player in &[agent, opponent] {
+        values.extend(KINDS.iter().map(|&kind| player.count(kind) as f32));
+    }
+    // Both sides are bucketed by their distance from the agent's base
+    let bucket = |distance: f32| ((distance*BUCKETS as f32) as usize).min(BUCKETS - 1);
+    let mut buckets = [0.0; 2*BUCKETS];
+    for unit in &agent.units {
+        buckets[bucket(unit.advance())] += 1.0;
+    }
+    for unit in &opponent.units {
+        buckets[BUCKETS + bucket(1.0 - unit.advance())] += 1.0;
+    }
+    values.extend(buckets.iter());
+    Observation{
+        values: values,
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn env() -> Env {
+        Env::new(GameData::bundled())
+    }
+
+    #[test]
+    fn observations_have_a_fixed_length() {
+        let mut env = env();
+        assert_eq!(env.reset(1).unwrap().values.len(), Observation::LEN);
+        let (observation, _, _, _) = env.step(Action::Ling).unwrap();
+        assert_eq!(observation.values.len(), Observation::LEN);
+    }
+
+    #[test]
+    fn nothing_happening_is_worth_nothing() {
+        let mut env = env();
+        env.opponent = Controltype::Human;
+        env.reset(1).unwrap();
+        let (_, reward, done, info) = env.step(Action::Noop).unwrap();
+        assert_eq!(reward, 0.0);
+        assert!(!done);
+        assert!(info.executed);
+    }
+
+    #[test]
+    fn unaffordable_actions_are_not_executed() {
+        let mut env = env();
+        env.reset(1).unwrap();
+        let (_, _, _, info) = env.step(Action::Bane).unwrap();
+        assert!(!info.executed);
+    }
+
+    #[test]
+    fn destroying_the_opponent_is_worth_its_base_and_the_win() {
+        let mut env = env();
+        env.opponent = Controltype::Human;
+        env.reset(1).unwrap();
+        let mut total = 0.0;
+        loop {
+            let (_, reward, done, info) = env.step(Action::Ling).unwrap();
+            total += reward;
+            if done {
+                assert_eq!(info.winner, Some(0));
+                break;
+            }
+        }
+        assert!((total - 2.0).abs() < 1e-3, "total reward {}", total);
+    }
+
+    #[test]
+    fn losing_costs_the_own_base_and_the_loss() {
+        let mut env = env();
+        env.reset(1).unwrap();
+        let mut total = 0.0;
+        loop {
+            let (_, reward, done, info) = env.step(Action::Noop).unwrap();
+            total += reward;
+            if done {
+                assert_eq!(info.winner, Some(1));
+                break;
+            }
+        }
+        assert!((total + 2.0).abs() < 1e-3, "total reward {}", total);
+    }
+}
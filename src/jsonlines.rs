@@ -0,0 +1,52 @@
+use ggez::{GameResult, GameError};
+use serde::Serialize;
+use serde::de::DeserializeOwned;
+use serde_json;
+
+use std::io::{BufRead, Write};
+use std::sync::mpsc::{self, Receiver};
+use std::thread;
+
+
+/// Seconds the other side has to answer during a handshake
+pub const HANDSHAKE_TIMEOUT: u64 = 10;
+
+/// Writes the message as a single line of json and flushes it
+pub fn write<W: Write, T: Serialize>(mut output: W, message: &T) -> GameResult<()> {
+    let line = serde_json::to_string(message)
+        .map_err(|e| GameError::UnknownError(format!("could not write message: {}", e)))?;
+    writeln!(output, "{}", line)?;
+    output.flush()?;
+    Ok(())
+}
+
+/// Reads the next line and parses it as a message, failing if the other side has closed the connection
+pub fn read<R: BufRead, T: DeserializeOwned>(input: &mut R) -> GameResult<T> {
+    let mut line = String::new();
+    if input.read_line(&mut line)? == 0 {
+        return Err(GameError::UnknownError(String::from("the connection was closed")));
+    }
+    parse(&line)
+}
+
+/// Parses a line the other side has written as a message
+pub fn parse<T: DeserializeOwned>(line: &str) -> GameResult<T> {
+    serde_json::from_str(line).map_err(|e| GameError::UnknownError(format!("invalid message {:?}: {}", line.trim(), e)))
+}
+
+/// Returns the lines of the input, read on a separate thread so the caller doesn't block on them.
+/// The receiver disconnects once the input is closed
+pub fn lines<R: BufRead + Send + 'static>(input: R) -> Receiver<String> {
+    let (sender, lines) = mpsc::channel();
+    thread::spawn(move || {
+        for line in input.lines() {
+            match line {
+                Ok(line) => if sender.send(line).is_err() {
+                    break;
+                },
+                Err(_) => break,
+            }
+        }
+    });
+    lines
+}
@@ -0,0 +1,49 @@
+//! The game model, the headless simulation and the tools built on it, shared by the game and the Python bindings
+
+pub mod traits;
+pub mod gameobject;
+pub mod gamestate;
+pub mod player;
+pub mod constants;
+pub mod upgrade;
+pub mod ability;
+pub mod status;
+pub mod objective;
+pub mod data;
+pub mod faction;
+pub mod dice;
+pub mod results;
+pub mod save;
+pub mod scenario;
+pub mod balance;
+pub mod simulation;
+pub mod sweep;
+pub mod tournament;
+pub mod evolution;
+pub mod gym;
+pub mod mcts;
+pub mod jsonlines;
+pub mod bot;
+pub mod server;
+pub mod lockstep;
+pub mod spectator;
+pub mod script;
+pub mod behavior;
+pub mod setup;
+
+#[cfg(feature = "python")]
+pub mod python;
+
+extern crate ggez;
+extern crate serde;
+#[macro_use]
+extern crate serde_derive;
+extern crate serde_json;
+extern crate rhai;
+#[cfg(feature = "python")]
+extern crate pyo3;
+#[cfg(feature = "python")]
+extern crate numpy;
+// The code generated by the pyo3 macros refers to `::core`
+#[cfg(feature = "python")]
+extern crate core;
@@ -0,0 +1,466 @@
+use ggez::{GameResult, GameError};
+use serde_json;
+
+use std::collections::BTreeMap;
+use std::io::{BufRead, BufReader};
+use std::mem;
+use std::net::{SocketAddr, TcpListener, TcpStream, ToSocketAddrs};
+use std::sync::mpsc::{Receiver, TryRecvError};
+use std::time::{Duration, Instant};
+
+use player::Command;
+use simulation::Simulation;
+use jsonlines::{self, HANDSHAKE_TIMEOUT};
+
+
+/// Version of the protocol, both peers have to speak the same
+pub const PROTOCOL_VERSION: u32 = 1;
+
+/// Seconds a lost peer has to reconnect before the match is given up
+const RECONNECT_TIMEOUT: u64 = 30;
+
+/// Milliseconds between two attempts of the joining peer to reconnect
+const RECONNECT_INTERVAL: u64 = 1000;
+
+/// What the hosting peer tells the joining one before the match starts, so both simulate the same match
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Start{
+    /// Name of the setup, see `Setup::from_name`, which has to have exactly two human players
+    pub mode: String,
+    pub factions: Vec<String>,
+    pub seed: u64,
+    /// Ticks between a command being issued and being carried out, hiding the latency between the peers
+    pub delay: u64,
+    /// Ticks between two comparisons of the simulation states
+    pub checksum_interval: u64,
+}
+
+/// A message between the peers, written as a single line of json
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+enum Message{
+    /// Sent by the joining peer when it connects
+    Join{
+        version: u32,
+    },
+    /// The host's answer to a valid `join`
+    Start{
+        version: u32,
+        start: Start,
+    },
+    /// The host's answer to an invalid `join`, after which it keeps waiting
+    Rejected{
+        reason: String,
+    },
+    /// Sent by the joining peer when it reconnects, with the last tick it has received the host's input for
+    Rejoin{
+        version: u32,
+        received: u64,
+    },
+    /// The host's answer to a `rejoin`, with the last tick it has received the joining peer's input for
+    Resume{
+        received: u64,
+    },
+    /// The commands of the sending peer's player for a tick, sent for every tick even if there are none
+    Input{
+        tick: u64,
+        commands: Vec<Command>,
+    },
+    /// Hash of the sending peer's simulation after a tick
+    Checksum{
+        tick: u64,
+        hash: u64,
+    },
+}
+
+/// How the peer reaches the other one again after the connection is lost
+#[derive(Debug)]
+enum Role{
+    /// Waits for the joining peer to reconnect
+    Host(TcpListener),
+    /// Reconnects to the host
+    Join(SocketAddr),
+}
+
+/// The open connection to the other peer
+#[derive(Debug)]
+struct Connection{
+    stream: TcpStream,
+    /// Lines the other peer has written, read on a separate thread so the game doesn't block
+    lines: Receiver<String>,
+}
+
+impl Connection {
+    fn new<R: BufRead + Send + 'static>(stream: TcpStream, input: R) -> Connection {
+        Connection{
+            stream: stream,
+            lines: jsonlines::lines(input),
+        }
+    }
+}
+
+/// Plays a match against another instance of the game over TCP in lockstep: both peers simulate the whole match
+/// and only exchange the commands of their human players, each carried out `delay` ticks after it was issued.
+/// A peer only advances once it knows the commands of both players for the next tick,
+/// and both compare hashes of their simulations every `checksum_interval` ticks to detect a desync
+#[derive(Debug)]
+pub struct Lockstep{
+    role: Role,
+    connection: Option<Connection>,
+    /// When the connection was lost, if it is
+    lost: Option<Instant>,
+    /// When the joining peer last tried to reconnect
+    attempt: Option<Instant>,
+    /// Index of the local player among the human players, the host being the first
+    pub local: usize,
+    pub start: Start,
+    /// Commands issued locally since the last input was sent
+    pending: Vec<Command>,
+    /// Every tick with local commands the other peer may not have carried out yet, kept to send them again after a reconnect
+    sent: BTreeMap<u64, Vec<Command>>,
+    /// The last tick the local input has been sent for
+    sent_until: u64,
+    /// Commands of the other peer for ticks not simulated yet
+    received: BTreeMap<u64, Vec<Command>>,
+    /// The last tick the other peer's input has been received for
+    received_until: u64,
+    /// Local and remote hashes by tick, awaiting their counterpart
+    checksums: BTreeMap<u64, u64>,
+    remote_checksums: BTreeMap<u64, u64>,
+}
+
+impl Lockstep {
+    /// Listens on the passed address, e.g. `127.0.0.1:7777`, returning the listener to host on and the address it got,
+    /// with a free port if the passed one is 0
+    pub fn listen(address: &str) -> GameResult<(TcpListener, SocketAddr)> {
+        let listener = TcpListener::bind(address)?;
+        let address = listener.local_addr()?;
+        Ok((listener, address))
+    }
+
+    /// Waits on the listener until another peer joins, then starts the passed match.
+    /// Peers with an invalid join are rejected and the host keeps waiting
+    pub fn host(listener: TcpListener, start: Start) -> GameResult<Lockstep> {
+        println!("Waiting for a player to join on {}", listener.local_addr()?);
+        loop {
+            let (stream, address) = listener.accept()?;
+            match welcome(stream, &start) {
+                Ok(connection) => {
+                    println!("{} joined the match", address);
+                    return Ok(Lockstep::new(Role::Host(listener), connection, 0, start));
+                }
+                Err(e) => println!("Rejected {}: {}", address, e),
+            }
+        }
+    }
+
+    /// Joins the match hosted at the passed address, e.g. `127.0.0.1:7777`
+    pub fn join(address: &str) -> GameResult<Lockstep> {
+        let address = address.to_socket_addrs()?.next()
+            .ok_or_else(|| GameError::UnknownError(format!("could not resolve {}", address)))?;
+        let stream = TcpStream::connect(address)?;
+        stream.set_read_timeout(Some(Duration::from_secs(HANDSHAKE_TIMEOUT)))?;
+        let mut input = BufReader::new(stream.try_clone()?);
+        jsonlines::write(&stream, &Message::Join{version: PROTOCOL_VERSION})?;
+        match jsonlines::read(&mut input)? {
+            Message::Start{version, start} => {
+                if version != PROTOCOL_VERSION {
+                    return Err(GameError::UnknownError(format!("the host speaks protocol version {}, expected {}", version, PROTOCOL_VERSION)));
+                }
+                stream.set_read_timeout(None)?;
+                Ok(Lockstep::new(Role::Join(address), Connection::new(stream, input), 1, start))
+            }
+            Message::Rejected{reason} => Err(GameError::UnknownError(format!("the host rejected the join: {}", reason))),
+            message => Err(GameError::UnknownError(format!("expected the match to start, got {:?}", message))),
+        }
+    }
+
+    fn new(role: Role, connection: Connection, local: usize, start: Start) -> Lockstep {
+        Lockstep{
+            role: role,
+            connection: Some(connection),
+            lost: None,
+            attempt: None,
+            local: local,
+            // No one can issue commands for the first ticks
+            sent_until: start.delay,
+            received_until: start.delay,
+            start: start,
+            pending: Vec::new(),
+            sent: BTreeMap::new(),
+            received: BTreeMap::new(),
+            checksums: BTreeMap::new(),
+            remote_checksums: BTreeMap::new(),
+        }
+    }
+
+    /// Queues a command of the local player, to be carried out once the input delay has passed
+    pub fn command(&mut self, command: Command) {
+        self.pending.push(command);
+    }
+
+    /// Advances the simulation by a tick if the commands of both players for it are known, returning whether it did.
+    /// Fails if the simulations of the peers have diverged or the other peer didn't reconnect in time
+    pub fn tick(&mut self, sim: &mut Simulation) -> GameResult<bool> {
+        self.receive()?;
+        if self.connection.is_none() && !self.reconnect()? {
+            return Ok(false);
+        }
+        let tick = sim.tick + 1;
+        // The local input is sent `delay` ticks ahead, carrying everything queued since the last one
+        while self.sent_until < tick + self.start.delay {
+            self.sent_until += 1;
+            let commands = mem::replace(&mut self.pending, Vec::new());
+            self.send(&Message::Input{tick: self.sent_until, commands: commands.clone()});
+            if !commands.is_empty() {
+                self.sent.insert(self.sent_until, commands);
+            }
+        }
+        if tick > self.received_until {
+            return Ok(false);
+        }
+        // Both peers carry out the commands in the same order, the host's first
+        let local = self.sent.get(&tick).cloned().unwrap_or_default();
+        let remote = self.received.remove(&tick).unwrap_or_default();
+        let (host, joined) = if self.local == 0 {(local, remote)} else {(remote, local)};
+        for command in host {
+            sim.command(0, command);
+        }
+        for command in joined {
+            sim.command(1, command);
+        }
+        sim.tick()?;
+        self.prune(sim.tick);
+        if sim.tick % self.start.checksum_interval.max(1) == 0 {
+            let hash = checksum(sim)?;
+            self.send(&Message::Checksum{tick: sim.tick, hash: hash});
+            self.checksums.insert(sim.tick, hash);
+            self.compare()?;
+        }
+        Ok(true)
+    }
+
+    /// Drops the local commands both peers have carried out, which are never sent again.
+    /// The other peer sends its input for a tick `delay` ticks ahead, before advancing to the tick after its last one,
+    /// so it has carried out every tick up to `delay + 1` ticks before the last input received from it
+    fn prune(&mut self, tick: u64) {
+        let confirmed = tick.min(self.received_until.saturating_sub(self.start.delay + 1));
+        self.sent = self.sent.split_off(&(confirmed + 1));
+    }
+
+    /// Handles the messages the other peer has sent since the last call
+    fn receive(&mut self) -> GameResult<()> {
+        loop {
+            let line = match self.connection {
+                Some(ref connection) => match connection.lines.try_recv() {
+                    Ok(line) => line,
+                    Err(TryRecvError::Empty) => return Ok(()),
+                    Err(TryRecvError::Disconnected) => {
+                        self.disconnect();
+                        return Ok(());
+                    }
+                },
+                None => return Ok(()),
+            };
+            match jsonlines::parse(&line)? {
+                Message::Input{tick, commands} => {
+                    // Inputs arrive in order, those sent again after a reconnect may already be known
+                    if tick == self.received_until + 1 {
+                        self.received_until = tick;
+                        if !commands.is_empty() {
+                            self.received.insert(tick, commands);
+                        }
+                    } else if tick > self.received_until {
+                        return Err(GameError::UnknownError(format!("expected the input for tick {}, got tick {}", self.received_until + 1, tick)));
+                    }
+                }
+                Message::Checksum{tick, hash} => {
+                    self.remote_checksums.insert(tick, hash);
+                    self.compare()?;
+                }
+                message => return Err(GameError::UnknownError(format!("unexpected message {:?}", message))),
+            }
+        }
+    }
+
+    /// Compares the hashes known of both peers, failing on the first that differs
+    fn compare(&mut self) -> GameResult<()> {
+        let ticks: Vec<u64> = self.checksums.keys().filter(|tick| self.remote_checksums.contains_key(tick)).cloned().collect();
+        for tick in ticks {
+            if self.checksums.remove(&tick) != self.remote_checksums.remove(&tick) {
+                return Err(GameError::UnknownError(format!("the simulations of the peers diverged at tick {}", tick)));
+            }
+        }
+        Ok(())
+    }
+
+    /// Sends a message to the other peer, treating the connection as lost if that fails
+    fn send(&mut self, message: &Message) {
+        let written = match self.connection {
+            Some(ref connection) => jsonlines::write(&connection.stream, message).is_ok(),
+            None => true,
+        };
+        if !written {
+            self.disconnect();
+        }
+    }
+
+    fn disconnect(&mut self) {
+        if self.connection.take().is_some() {
+            println!("Lost the connection to the other player, waiting {} seconds for it to come back", RECONNECT_TIMEOUT);
+            self.lost = Some(Instant::now());
+        }
+    }
+
+    /// Tries to connect to the other peer again, returning whether it has.
+    /// The peers then send each other the inputs the other hasn't received yet
+    fn reconnect(&mut self) -> GameResult<bool> {
+        let lost = self.lost.map(|lost| lost.elapsed()).unwrap_or_default();
+        if lost > Duration::from_secs(RECONNECT_TIMEOUT) {
+            return Err(GameError::UnknownError(format!("the other player did not come back within {} seconds", RECONNECT_TIMEOUT)));
+        }
+        let resumed = match self.role {
+            Role::Host(ref listener) => {
+                listener.set_nonblocking(true)?;
+                let accepted = listener.accept();
+                listener.set_nonblocking(false)?;
+                match accepted {
+                    Ok((stream, _)) => rejoined(stream, self.received_until),
+                    Err(_) => return Ok(false),
+                }
+            }
+            Role::Join(address) => {
+                let due = self.attempt.map(|attempt| attempt.elapsed() >= Duration::from_millis(RECONNECT_INTERVAL)).unwrap_or(true);
+                if !due {
+                    return Ok(false);
+                }
+                self.attempt = Some(Instant::now());
+                rejoin(address, self.received_until)
+            }
+        };
+        let (connection, received) = match resumed {
+            Ok(resumed) => resumed,
+            Err(e) => {
+                println!("Could not reconnect: {}", e);
+                return Ok(false);
+            }
+        };
+        println!("Reconnected to the other player");
+        self.connection = Some(connection);
+        self.lost = None;
+        for tick in received + 1..self.sent_until + 1 {
+            let commands = self.sent.get(&tick).cloned().unwrap_or_default();
+            self.send(&Message::Input{tick: tick, commands: commands});
+        }
+        // Hashes lost with the connection won't be compared anymore
+        self.checksums.clear();
+        self.remote_checksums.clear();
+        Ok(self.connection.is_some())
+    }
+}
+
+/// Reads the join of a freshly connected peer and answers it with the match to start
+fn welcome(stream: TcpStream, start: &Start) -> GameResult<Connection> {
+    stream.set_read_timeout(Some(Duration::from_secs(HANDSHAKE_TIMEOUT)))?;
+    let mut input = BufReader::new(stream.try_clone()?);
+    let joined = match jsonlines::read(&mut input)? {
+        Message::Join{version} if version == PROTOCOL_VERSION => Ok(()),
+        Message::Join{version} => Err(format!("protocol version {} is not supported, expected {}", version, PROTOCOL_VERSION)),
+        message => Err(format!("expected a join, got {:?}", message)),
+    };
+    match joined {
+        Ok(()) => {
+            jsonlines::write(&stream, &Message::Start{version: PROTOCOL_VERSION, start: start.clone()})?;
+            stream.set_read_timeout(None)?;
+            Ok(Connection::new(stream, input))
+        }
+        Err(reason) => {
+            let _ = jsonlines::write(&stream, &Message::Rejected{reason: reason.clone()});
+            Err(GameError::UnknownError(reason))
+        }
+    }
+}
+
+/// Reads the rejoin of the joining peer that has reconnected, returning the last tick it has received
+fn rejoined(stream: TcpStream, received: u64) -> GameResult<(Connection, u64)> {
+    stream.set_nonblocking(false)?;
+    stream.set_read_timeout(Some(Duration::from_secs(HANDSHAKE_TIMEOUT)))?;
+    let mut input = BufReader::new(stream.try_clone()?);
+    match jsonlines::read(&mut input)? {
+        Message::Rejoin{version, received: remote} if version == PROTOCOL_VERSION => {
+            jsonlines::write(&stream, &Message::Resume{received: received})?;
+            stream.set_read_timeout(None)?;
+            Ok((Connection::new(stream, input), remote))
+        }
+        message => Err(GameError::UnknownError(format!("expected a rejoin, got {:?}", message))),
+    }
+}
+
+/// Reconnects to the host, returning the last tick it has received
+fn rejoin(address: SocketAddr, received: u64) -> GameResult<(Connection, u64)> {
+    let stream = TcpStream::connect_timeout(&address, Duration::from_millis(RECONNECT_INTERVAL))?;
+    stream.set_read_timeout(Some(Duration::from_secs(HANDSHAKE_TIMEOUT)))?;
+    let mut input = BufReader::new(stream.try_clone()?);
+    jsonlines::write(&stream, &Message::Rejoin{version: PROTOCOL_VERSION, received: received})?;
+    match jsonlines::read(&mut input)? {
+        Message::Resume{received: remote} => {
+            stream.set_read_timeout(None)?;
+            Ok((Connection::new(stream, input), remote))
+        }
+        message => Err(GameError::UnknownError(format!("expected the match to resume, got {:?}", message))),
+    }
+}
+
+/// Returns the FNV-1a hash of the saved simulation, equal on both peers as long as they are in sync
+fn checksum(sim: &Simulation) -> GameResult<u64> {
+    let json = serde_json::to_string(&sim.save())
+        .map_err(|e| GameError::UnknownError(format!("could not hash the simulation: {}", e)))?;
+    Ok(json.bytes().fold(0xcbf2_9ce4_8422_2325, |hash, byte| (hash ^ byte as u64).wrapping_mul(0x100_0000_01b3)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use data::GameData;
+    use setup::Setup;
+    use std::thread;
+
+    fn start() -> Start {
+        Start{
+            mode: String::from("1v1"),
+            factions: vec![String::from("zerg"), String::from("terran")],
+            seed: 3,
+            delay: 6,
+            checksum_interval: 60,
+        }
+    }
+
+    fn simulation(start: &Start) -> Simulation {
+        let data = GameData::bundled();
+        let mut setup = Setup::from_name(&start.mode).unwrap();
+        let factions: Vec<&str> = start.factions.iter().map(|faction| faction.as_str()).collect();
+        setup.set_factions(&factions);
+        Simulation::new(&setup, &data, start.seed).unwrap()
+    }
+
+    #[test]
+    fn peers_stay_in_sync_and_forget_confirmed_commands() {
+        let (listener, address) = Lockstep::listen("127.0.0.1:0").unwrap();
+        let host = thread::spawn(|| Lockstep::host(listener, start()).unwrap());
+        let joined = Lockstep::join(&address.to_string()).unwrap();
+        let mut peers = [host.join().unwrap(), joined];
+        let mut sims = [simulation(&peers[0].start), simulation(&peers[1].start)];
+        while sims.iter().any(|sim| sim.tick < 300) {
+            for (net, sim) in peers.iter_mut().zip(sims.iter_mut()) {
+                if sim.tick < 300 {
+                    net.command(if sim.tick % 2 == 0 {Command::Advance} else {Command::Retreat});
+                    net.tick(sim).unwrap();
+                }
+            }
+        }
+        assert_eq!(checksum(&sims[0]).unwrap(), checksum(&sims[1]).unwrap());
+        for net in &peers {
+            assert!(net.sent.len() as u64 <= 2*net.start.delay + 2, "{} ticks of commands kept", net.sent.len());
+        }
+    }
+}
@@ -1,22 +1,39 @@
-mod traits;
-mod gameobject;
-mod gamestate;
-mod player;
-mod constants;
-
 extern crate ggez;
 extern crate reqwest;
+extern crate serde_json;
+extern crate zerg_warz;
 
 use ggez::event;
-use ggez::{Context};
+use ggez::{Context, GameResult, GameError};
 use ggez::conf;
 
 use std::{env, path};
+use std::time::{SystemTime, UNIX_EPOCH};
 use std::fs::File;
 use std::io::{Read, Write};
 
 
-use gamestate::GameState;
+use zerg_warz::gamestate::GameState;
+use zerg_warz::setup::Setup;
+use zerg_warz::save::SaveGame;
+use zerg_warz::scenario::Scenario;
+use zerg_warz::data::GameData;
+use zerg_warz::balance::{Matrix, Composition, unit_compositions, fair_budget};
+use zerg_warz::sweep::{Sweep, Grid};
+use zerg_warz::tournament::{Tournament, Entrant, Ratings, Schedule};
+use zerg_warz::constants::SimConfig;
+use zerg_warz::evolution::Population;
+use zerg_warz::gym::{Env, Action};
+use zerg_warz::dice::{Dice, Randomness};
+use zerg_warz::player::Controltype;
+use zerg_warz::bot::{Bot, BotParams};
+use zerg_warz::server::Server;
+use zerg_warz::lockstep::{Lockstep, Start};
+use zerg_warz::spectator::{Broadcast, Spectator};
+use zerg_warz::script::ScriptParams;
+use zerg_warz::behavior::BehaviorTree;
+
+use std::process;
 
 
 fn download_assets(files: Vec<(&str, &str)>){ //consuming
@@ -34,6 +51,206 @@ fn download_assets(files: Vec<(&str, &str)>){ //consuming
 }
 
 
+/// Returns the value following the passed option, e.g. `1v1` for `--mode 1v1`
+fn arg<'a>(args: &'a [String], name: &str) -> Option<&'a String> {
+    args.iter().position(|arg| arg == name).and_then(|i| args.get(i + 1))
+}
+
+/// Fights all unit kinds, or the compositions in the file passed with `--compositions`, against each other
+/// without opening a window, and writes the results to `balance.csv` and `balance.md`
+fn run_matrix(args: &[String], seed: u64) -> GameResult<()> {
+    let data = GameData::from_reader(File::open("assets/gamedata.json")?)?;
+    let compositions = match arg(args, "--compositions") {
+        Some(path) => Composition::read(path)?,
+        None => unit_compositions(&data),
+    };
+    let fights = arg(args, "--fights").and_then(|fights| fights.parse().ok()).unwrap_or(20);
+    let budget = arg(args, "--budget").and_then(|budget| budget.parse().ok()).unwrap_or(1000);
+    let budget = fair_budget(&data, &compositions, budget)?;
+    let matrix = Matrix::run(&data, compositions, budget, fights, seed)?;
+    println!("{}", matrix.to_markdown());
+    matrix.write("balance.csv", "balance.md")
+}
+
+/// Plays AI-vs-AI batches of the passed setup for every combination of scales, in parallel and without opening a window,
+/// and writes the win rates per unit kind to `sweep.csv` and `sweep.md`
+fn run_sweep(args: &[String], setup: &Setup, seed: u64) -> GameResult<()> {
+    let data = GameData::from_reader(File::open("assets/gamedata.json")?)?;
+    let mut grid = Grid::around(SimConfig::default());
+    //each scale takes a comma separated list of values, e.g. `--damage-scale 0.005,0.01`
+    let values = |name: &str| arg(args, name).map(|list| list.split(',').filter_map(|value| value.parse().ok()).collect());
+    if let Some(values) = values("--movement-speed") {
+        grid.movement_speed = values;
+    }
+    if let Some(values) = values("--damage-scale") {
+        grid.damage_scale = values;
+    }
+    if let Some(values) = values("--map-scale") {
+        grid.map_scale = values;
+    }
+    let games = arg(args, "--games").and_then(|games| games.parse().ok()).unwrap_or(10);
+    let threads = arg(args, "--threads").and_then(|threads| threads.parse().ok()).unwrap_or(4);
+    let mut setup = setup.clone();
+    for slot in &mut setup.slots {
+        slot.ai = true;
+    }
+    let sweep = Sweep::run(&data, &setup, &grid, games, seed, threads)?;
+    println!("{}", sweep.to_markdown());
+    sweep.write("sweep.csv", "sweep.md")
+}
+
+/// Plays a tournament between the entrants in `entrants.json`, or the file passed with `--entrants`,
+/// and updates their ratings in `ratings.json`, or the file passed with `--ratings`
+fn run_tournament(args: &[String], seed: u64) -> GameResult<()> {
+    let data = GameData::from_reader(File::open("assets/gamedata.json")?)?;
+    let entrants = Entrant::read(arg(args, "--entrants").map(|path| path.as_str()).unwrap_or("entrants.json"))?;
+    let schedule = match arg(args, "--schedule").map(|schedule| schedule.as_str()) {
+        Some("swiss") => Schedule::Swiss(arg(args, "--rounds").and_then(|rounds| rounds.parse().ok()).unwrap_or(3)),
+        _ => Schedule::RoundRobin,
+    };
+    let tournament = Tournament{
+        entrants: entrants,
+        maps: arg(args, "--maps").map(|maps| maps.as_str()).unwrap_or("1v1,ffa2").split(',').map(String::from).collect(),
+        seeds: arg(args, "--seeds").and_then(|seeds| seeds.parse().ok()).unwrap_or(2),
+        seed: seed,
+    };
+    let path = arg(args, "--ratings").map(|path| path.as_str()).unwrap_or("ratings.json");
+    let mut ratings = Ratings::read(path)?;
+    let points = tournament.run(&data, schedule, &mut ratings)?;
+    println!("\nTournament points:");
+    for (entrant, points) in tournament.entrants.iter().zip(points) {
+        println!("{}: {}", entrant.name, points);
+    }
+    println!("\n{}", ratings.leaderboard());
+    ratings.write(path)
+}
+
+/// Evolves the thresholds of the `SimpleAI` without opening a window, continuing the population in
+/// `population.json`, or the file passed with `--population`, if there is one.
+/// The file is rewritten after every generation, best genome first
+fn run_evolution(args: &[String], seed: u64) -> GameResult<()> {
+    let data = GameData::from_reader(File::open("assets/gamedata.json")?)?;
+    let path = arg(args, "--population").map(|path| path.as_str()).unwrap_or("population.json");
+    let size = arg(args, "--size").and_then(|size| size.parse().ok()).unwrap_or(8);
+    let generations: u32 = arg(args, "--generations").and_then(|generations| generations.parse().ok()).unwrap_or(5);
+    let mutation = arg(args, "--mutation").and_then(|mutation| mutation.parse().ok()).unwrap_or(0.5);
+    let maps: Vec<String> = arg(args, "--maps").map(|maps| maps.as_str()).unwrap_or("1v1").split(',').map(String::from).collect();
+    let seeds = arg(args, "--seeds").and_then(|seeds| seeds.parse().ok()).unwrap_or(1);
+    let mut dice = Dice::new(seed, Randomness::default());
+    let mut population = if path::Path::new(path).exists() {
+        // The file holds an evaluated generation, carry on with its offspring
+        let mut population = Population::read(path)?;
+        population.breed(mutation, &mut dice);
+        population
+    } else {
+        let faction = arg(args, "--faction").map(|faction| faction.as_str()).unwrap_or("zerg");
+        Population::new(size, faction, mutation, &mut dice)
+    };
+    for n in 0..generations {
+        if n > 0 {
+            population.breed(mutation, &mut dice);
+        }
+        // Every generation plays with fresh seeds
+        let generation_seed = seed.wrapping_add(population.generation as u64*seeds as u64);
+        population.evaluate(&data, &maps, seeds, generation_seed)?;
+        println!("\nGeneration {}: best fitness {:.2}, mean fitness {:.2}", population.generation + 1,
+            population.genomes[0].fitness, population.mean_fitness());
+        println!("{}", serde_json::to_string(&population.genomes[0].params).unwrap_or_default());
+        population.write(path)?;
+    }
+    Ok(())
+}
+
+/// Plays episodes of the reinforcement learning environment with an agent that picks random actions,
+/// printing the return and length of each, as a baseline for trained agents
+fn run_gym(args: &[String], seed: u64) -> GameResult<()> {
+    let data = GameData::from_reader(File::open("assets/gamedata.json")?)?;
+    let episodes = arg(args, "--episodes").and_then(|episodes| episodes.parse().ok()).unwrap_or(10);
+    let mut env = Env::new(data);
+    let mut dice = Dice::new(seed, Randomness::default());
+    let mut total = 0.0;
+    for episode in 0..episodes {
+        env.reset(seed.wrapping_add(episode))?;
+        let (mut reward, mut steps) = (0.0, 0);
+        loop {
+            let action = Action::from_index((dice.roll()*Action::COUNT as f32) as usize).unwrap_or(Action::Noop);
+            let (_, step_reward, done, info) = env.step(action)?;
+            reward += step_reward;
+            steps += 1;
+            if done {
+                println!("Episode {}: return {:.2} after {} steps, winner {:?}", episode + 1, reward, steps, info.winner);
+                break;
+            }
+        }
+        total += reward;
+    }
+    println!("Average return of the random agent: {:.2}", total/episodes.max(1) as f32);
+    Ok(())
+}
+
+/// Returns the params of the bots on the command line, with their timing passed as `--bot-interval` and `--bot-timeout`
+fn bot_params(args: &[String], command: Option<String>, bot_args: Vec<String>) -> BotParams {
+    BotParams{
+        command: command,
+        args: bot_args,
+        interval: arg(args, "--bot-interval").and_then(|interval| interval.parse().ok()).unwrap_or(1.0),
+        timeout: arg(args, "--bot-timeout").and_then(|timeout| timeout.parse().ok()).unwrap_or(1000),
+    }
+}
+
+/// Waits for bots to claim the AI players of the setup over TCP on the passed port of localhost
+fn accept_bots(setup: &Setup, port: &str) -> GameResult<Vec<(usize, Bot)>> {
+    let port = port.parse().map_err(|_| GameError::UnknownError(format!("invalid port {}", port)))?;
+    let server = Server::bind(port)?;
+    let players: Vec<usize> = setup.slots.iter().enumerate().filter(|&(_, slot)| slot.ai).map(|(i, _)| i).collect();
+    let names: Vec<String> = players.iter().map(|player| (player + 1).to_string()).collect();
+    println!("Waiting for bots to claim players {} on 127.0.0.1:{}", names.join(", "), server.port()?);
+    server.accept(&players)
+}
+
+/// Returns the passed address and port like `0.0.0.0:7777`, or the passed port of localhost if it is only a port
+fn listen_address(address: &str) -> String {
+    match address.parse::<u16>() {
+        Ok(port) => format!("127.0.0.1:{}", port),
+        Err(_) => address.to_string(),
+    }
+}
+
+/// Hosts a networked match of the passed setup on the passed address, see `listen_address`,
+/// with the input delay passed as `--input-delay` and the ticks between desync checks as `--checksum-interval`
+fn host(args: &[String], mode: &str, setup: &Setup, seed: u64, address: &str) -> GameResult<Lockstep> {
+    if setup.slots.iter().filter(|slot| !slot.ai).count() != 2 {
+        return Err(GameError::UnknownError(format!("mode {} doesn't have two human players", mode)));
+    }
+    let (listener, _) = Lockstep::listen(&listen_address(address))?;
+    Lockstep::host(listener, Start{
+        mode: mode.to_string(),
+        factions: setup.slots.iter().map(|slot| slot.faction.clone()).collect(),
+        seed: seed,
+        delay: arg(args, "--input-delay").and_then(|delay| delay.parse().ok()).unwrap_or(6),
+        checksum_interval: arg(args, "--checksum-interval").and_then(|interval| interval.parse().ok()).unwrap_or(60),
+    })
+}
+
+/// Joins the networked match hosted at the passed address, returning it with the setup the host picked
+fn join(address: &str) -> GameResult<(Lockstep, Setup)> {
+    let net = Lockstep::join(address)?;
+    let mut setup = Setup::from_name(&net.start.mode)
+        .ok_or_else(|| GameError::UnknownError(format!("the host picked the unknown mode {}", net.start.mode)))?;
+    {
+        let factions: Vec<&str> = net.start.factions.iter().map(|faction| faction.as_str()).collect();
+        setup.set_factions(&factions);
+    }
+    Ok((net, setup))
+}
+
+/// Broadcasts the match to spectators on the passed address, see `listen_address`,
+/// sending them a snapshot every `--snapshot-interval` ticks
+fn broadcast(args: &[String], address: &str) -> GameResult<Broadcast> {
+    Broadcast::bind(&listen_address(address), arg(args, "--snapshot-interval").and_then(|interval| interval.parse().ok()).unwrap_or(3))
+}
+
+
 fn main() {
 
     //move this to a  json file and loaded at launch instead
@@ -50,6 +267,156 @@ fn main() {
     download_assets(required_assets);
 
 
+    //pick the match setup, e.g. `--mode 2v2`
+    let args: Vec<String> = env::args().collect();
+    let mode = arg(&args, "--mode").map(|mode| mode.as_str()).unwrap_or("1v1");
+    let mut setup = match Setup::from_name(mode) {
+        Some(setup) => setup,
+        None => {
+            println!("Unknown mode {}, expected one of 1v1, 1vai, aivai, 2v2, ffa2, ffa3, ffa4", mode);
+            return;
+        }
+    };
+    //factions are assigned to the players in order, e.g. `--factions zerg,terran`
+    if let Some(factions) = arg(&args, "--factions") {
+        let factions: Vec<&str> = factions.split(',').collect();
+        setup.set_factions(&factions);
+    }
+    //AI players can be handed to an external program, e.g. `--mode 1vai --bot "python3 bots/example_bot.py"`,
+    //or wait for bots to connect over TCP, e.g. `--mode 1vai --serve 7777`
+    let serve = arg(&args, "--serve");
+    if let Some(bot) = arg(&args, "--bot") {
+        let mut words = bot.split_whitespace().map(String::from);
+        if let Some(command) = words.next() {
+            setup.set_controller(&Controltype::External(bot_params(&args, Some(command), words.collect())));
+        }
+    } else if serve.is_some() {
+        setup.set_controller(&Controltype::External(bot_params(&args, None, Vec::new())));
+    }
+    //or to a Rhai script, e.g. `--mode 1vai --script scripts/counter.rhai`, reloaded whenever the file changes
+    if let Some(path) = arg(&args, "--script") {
+        setup.set_controller(&Controltype::Script(ScriptParams{
+            path: path.clone(),
+            interval: arg(&args, "--script-interval").and_then(|interval| interval.parse().ok()).unwrap_or(0.5),
+        }));
+    }
+    //or to a behavior tree, e.g. `--mode 1vai --tree trees/counter.json`, press F3 in game to see its decisions
+    if let Some(path) = arg(&args, "--tree") {
+        match BehaviorTree::read(path) {
+            Ok(tree) => setup.set_controller(&Controltype::BehaviorTree(tree)),
+            Err(e) => {
+                println!("Could not load the behavior tree {}: {}", path, e);
+                return;
+            }
+        }
+    }
+    //pass `--seed <n>` to replay a match, a fresh seed is picked otherwise
+    let seed = match arg(&args, "--seed") {
+        Some(seed) => match seed.parse() {
+            Ok(seed) => seed,
+            Err(_) => {
+                println!("Invalid seed {}, expected a positive integer", seed);
+                return;
+            }
+        },
+        None => SystemTime::now().duration_since(UNIX_EPOCH).map(|time| time.as_secs() ^ time.subsec_nanos() as u64).unwrap_or(0),
+    };
+    //the headless tools derive the seeds of all their games from it
+    if ["--matrix", "--sweep", "--tournament", "--evolve", "--gym"].iter().any(|tool| args.iter().any(|arg| arg == tool)) {
+        println!("Seed: {}", seed);
+    }
+
+    //balance testing doesn't need a window, e.g. `--matrix --fights 20 --budget 1000`
+    if args.iter().any(|arg| arg == "--matrix") {
+        if let Err(e) = run_matrix(&args, seed) {
+            println!("Could not compute the matchup matrix: {}", e);
+            process::exit(1);
+        }
+        return;
+    }
+    //and neither does sweeping the scale constants, e.g. `--sweep --games 10 --damage-scale 0.005,0.01,0.02`
+    if args.iter().any(|arg| arg == "--sweep") {
+        if let Err(e) = run_sweep(&args, &setup, seed) {
+            println!("Could not run the sweep: {}", e);
+            process::exit(1);
+        }
+        return;
+    }
+    //pit AIs against each other, e.g. `--tournament --schedule swiss --rounds 3`
+    if args.iter().any(|arg| arg == "--tournament") {
+        if let Err(e) = run_tournament(&args, seed) {
+            println!("Could not run the tournament: {}", e);
+            process::exit(1);
+        }
+        return;
+    }
+
+    //and evolving AIs, e.g. `--evolve --generations 10 --size 8`
+    if args.iter().any(|arg| arg == "--evolve") {
+        if let Err(e) = run_evolution(&args, seed) {
+            println!("Could not evolve the AI: {}", e);
+            process::exit(1);
+        }
+        return;
+    }
+
+    //or playing the reinforcement learning environment with a random agent, e.g. `--gym --episodes 10`
+    if args.iter().any(|arg| arg == "--gym") {
+        if let Err(e) = run_gym(&args, seed) {
+            println!("Could not run the environment: {}", e);
+            process::exit(1);
+        }
+        return;
+    }
+
+    //bots claim their players before the window opens
+    let bots = match serve {
+        Some(port) => match accept_bots(&setup, port) {
+            Ok(bots) => bots,
+            Err(e) => {
+                println!("Could not serve bots: {}", e);
+                process::exit(1);
+            }
+        },
+        None => Vec::new(),
+    };
+
+    //play against another instance over the network, e.g. `--host 7777` and `--join 127.0.0.1:7777`.
+    //the host picks the mode, factions and seed
+    let net = match (arg(&args, "--host"), arg(&args, "--join")) {
+        (Some(address), _) => host(&args, mode, &setup, seed, address).map(Some),
+        (None, Some(address)) => join(address).map(|(net, joined)| {
+            setup = joined;
+            Some(net)
+        }),
+        (None, None) => Ok(None),
+    };
+    let net = match net {
+        Ok(net) => net,
+        Err(e) => {
+            println!("Could not start the networked match: {}", e);
+            process::exit(1);
+        }
+    };
+
+    //any match can be watched by spectators, e.g. `--mode aivai --broadcast 7778` and `--spectate 127.0.0.1:7778`
+    let broadcast = match arg(&args, "--broadcast").map(|address| broadcast(&args, address)) {
+        Some(Ok(broadcast)) => Some(broadcast),
+        Some(Err(e)) => {
+            println!("Could not broadcast the match: {}", e);
+            process::exit(1);
+        }
+        None => None,
+    };
+    let spectator = match arg(&args, "--spectate").map(|address| Spectator::connect(address)) {
+        Some(Ok(spectator)) => Some(spectator),
+        Some(Err(e)) => {
+            println!("Could not spectate the match: {}", e);
+            process::exit(1);
+        }
+        None => None,
+    };
+
     let c = conf::Conf::new();
     let ctx = &mut Context::load_from_conf("helloworld", "ggez", c).unwrap();
 
@@ -61,8 +428,42 @@ fn main() {
         ctx.filesystem.mount(&path, true)
     }
 
-    //create gamestate
-    let state = &mut GameState::new(ctx).unwrap();
+    //create gamestate, resuming a saved match when passed `--load <file>`
+    //or playing a scenario when passed `--scenario <file>`, unless spectating
+    let load = arg(&args, "--load");
+    let scenario = arg(&args, "--scenario");
+    let state = match (spectator, load, scenario) {
+        (Some((spectator, save)), _, _) => GameState::spectate(ctx, spectator, save)
+            .map_err(|e| format!("Could not spectate the match: {}", e)),
+        (None, Some(path), _) => SaveGame::read(path).and_then(|save| GameState::from_save(ctx, save))
+            .map_err(|e| format!("Could not load {}: {}", path, e)),
+        (None, None, Some(path)) => Scenario::read(path).and_then(|scenario| GameState::with_scenario(ctx, &scenario, seed))
+            .map_err(|e| format!("Could not play the scenario {}: {}", path, e)),
+        (None, None, None) => GameState::new(ctx, &setup, net.as_ref().map(|net| net.start.seed).unwrap_or(seed))
+            .map_err(|e| format!("Could not start the match: {}", e)),
+    };
+    let state = &mut match state {
+        Ok(state) => state,
+        Err(e) => {
+            println!("{}", e);
+            process::exit(1);
+        }
+    };
+    //the scenario, save or host may have picked the seed, this is the one that replays the match
+    println!("Match seed: {}", state.seed());
+    if let Some(net) = net {
+        state.connect(net);
+    }
+    if let Some(broadcast) = broadcast {
+        state.broadcast(broadcast);
+    }
+    //watch without issuing commands, e.g. `--mode aivai --observe`
+    if args.iter().any(|arg| arg == "--observe") {
+        state.observe();
+    }
+    for (player, bot) in bots {
+        state.attach_bot(player, bot);
+    }
 
     //run gamestate
     if let Err(e) = event::run(ctx, state){
@@ -70,6 +471,10 @@ fn main() {
     }else{
         println!("Game exited cleanly.");
     }
+    //failed scenarios fail the run, so they can be used as regression tests
+    if state.scenario_passed() == Some(false) {
+        process::exit(1);
+    }
 }
 
 
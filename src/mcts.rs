@@ -0,0 +1,274 @@
+use std::time::{Duration, Instant};
+
+use player::{Player, Command, Controltype, AiParams};
+use gameobject::Order;
+use dice::Dice;
+use simulation::Simulation;
+use constants::TICKS_PER_SECOND;
+
+
+/// Minerals a single hp of a base is worth when judging a position
+const BASE_HP_VALUE: f32 = 10.0;
+
+/// Share of their cost structures count for when judging a position, as they can't push towards the enemy base
+const STRUCTURE_VALUE: f32 = 0.5;
+
+/// Position the army holds at when the search decides to defend, measured like `GameChar::advance()`
+const DEFENSIVE_POSITION: f32 = 0.15;
+
+/// Settings of the `Mcts` controller, trading strength for the time each decision takes
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct MctsParams{
+    /// Seconds between two decisions
+    pub interval: f32,
+    /// Rollouts per decision
+    pub iterations: u32,
+    /// Stops searching after this many milliseconds, even if not all rollouts are done.
+    /// Matches are no longer reproducible from their seed once it is set
+    pub time_limit: Option<u64>,
+    /// Number of consecutive decisions searched, after which the rollout continues with the `SimpleAI`
+    pub depth: u32,
+    /// Seconds each rollout looks ahead
+    pub horizon: f32,
+    /// How much the search favours rarely tried actions over the best ones so far
+    pub exploration: f32,
+}
+
+impl Default for MctsParams {
+    fn default() -> MctsParams {
+        MctsParams{
+            interval: 2.0,
+            iterations: 24,
+            time_limit: None,
+            depth: 2,
+            horizon: 30.0,
+            exploration: 1.4,
+        }
+    }
+}
+
+impl MctsParams {
+    /// Returns the number of ticks between two decisions
+    pub fn interval_ticks(&self) -> u64 {
+        ((self.interval*TICKS_PER_SECOND as f32) as u64).max(1)
+    }
+}
+
+/// A decision in the search tree: the action taken at its depth, with the outcomes of the rollouts through it
+struct Node{
+    /// `None` waits, saving up minerals
+    action: Option<Command>,
+    children: Vec<usize>,
+    visits: u32,
+    /// Sum of the outcomes of all rollouts through the node, each between 0.0 and 1.0
+    value: f32,
+}
+
+/// Returns the next decision of the player at the passed index, found by a Monte Carlo tree search, `None` to wait.
+/// Deciding to spawn a unit means spawning it whenever affordable until the next decision.
+/// Each rollout plays a clone of the match forward, taking the decisions along a path of the tree
+/// and continuing with the `SimpleAI` for the rest of the horizon
+pub fn decide(sim: &Simulation, player: usize, params: &MctsParams) -> Option<Command> {
+    let actions = candidates(&sim.players[player]);
+    let mut nodes = vec!(Node{action: None, children: Vec::new(), visits: 0, value: 0.0});
+    let start = Instant::now();
+    for iteration in 0..params.iterations {
+        if let Some(limit) = params.time_limit {
+            if start.elapsed() >= Duration::from_millis(limit) {
+                break;
+            }
+        }
+        // Select the most promising path down the tree, expanding the first node with untried actions
+        let mut path = vec!(0);
+        while path.len() <= params.depth as usize {
+            let current = *path.last().unwrap();
+            if nodes[current].children.len() < actions.len() {
+                let action = actions[nodes[current].children.len()];
+                nodes.push(Node{action: action, children: Vec::new(), visits: 0, value: 0.0});
+                let child = nodes.len() - 1;
+                nodes[current].children.push(child);
+                path.push(child);
+                break;
+            }
+            let parent_visits = nodes[current].visits.max(1) as f32;
+            let best = nodes[current].children.iter().cloned().max_by(|&a, &b| {
+                let ucb = |node: &Node| node.value/node.visits.max(1) as f32
+                    + params.exploration*(parent_visits.ln()/node.visits.max(1) as f32).sqrt();
+                ucb(&nodes[a]).partial_cmp(&ucb(&nodes[b])).unwrap()
+            });
+            match best {
+                Some(child) => path.push(child),
+                None => break,
+            }
+        }
+
+        let decisions: Vec<Option<Command>> = path[1..].iter().map(|&node| nodes[node].action).collect();
+        let outcome = rollout(sim, player, &decisions, params, iteration);
+        for &node in &path {
+            nodes[node].visits += 1;
+            nodes[node].value += outcome;
+        }
+    }
+
+    // The most visited action is the most reliable one
+    let best = nodes[0].children.iter().cloned().max_by_key(|&child| nodes[child].visits);
+    best.and_then(|child| nodes[child].action)
+}
+
+/// Returns everything the player can do at a decision: wait, keep spawning any of its units or structures,
+/// or change the order of its army
+fn candidates(player: &Player) -> Vec<Option<Command>> {
+    let mut actions = vec!(None);
+    actions.extend(player.roster.iter().map(|def| Some(Command::Spawn(def.kind))));
+    if player.order != Order::Advance {
+        actions.push(Some(Command::Advance));
+    }
+    if player.order != Order::Hold(DEFENSIVE_POSITION) {
+        actions.push(Some(Command::Hold(DEFENSIVE_POSITION)));
+    }
+    actions
+}
+
+/// Plays a clone of the match forward for the horizon of the search, taking the passed decisions one interval apart.
+/// Returns how good the outcome is for the player, between 0.0 for a loss and 1.0 for a win
+fn rollout(sim: &Simulation, player: usize, decisions: &[Option<Command>], params: &MctsParams, iteration: u32) -> f32 {
+    let mut sim = sim.clone();
+    // Every rollout rolls its own dice, so the search sees different outcomes of the same decisions
+    let seed = sim.dice.seed() ^ sim.tick.wrapping_mul(0x9E37_79B9) ^ ((iteration as u64) << 32);
+    sim.dice = Dice::new(seed, sim.dice.randomness);
+    // Searching within a rollout would take forever and the clone doesn't share the bots and scripts of the real match,
+    // every other searching player, every bot and every script plays like the `SimpleAI`
+    for other in &mut sim.players {
+        match other.controltype {
+            Controltype::Mcts{..} | Controltype::External(_) | Controltype::Script(_) => {
+                other.controltype = Controltype::SimpleAI(AiParams::default())
+            }
+            _ => (),
+        }
+    }
+
+    let horizon = (params.horizon*TICKS_PER_SECOND as f32) as u64;
+    let mut ticks = 0;
+    for decision in decisions {
+        // Spawning is kept up for the whole interval, army orders are given once
+        sim.players[player].controltype = match *decision {
+            Some(Command::Spawn(kind)) => Controltype::Scripted{build: vec!(kind)},
+            Some(command) => {
+                let _ = sim.players[player].execute(command, &mut sim.dice);
+                Controltype::Human
+            }
+            None => Controltype::Human,
+        };
+        for _ in 0..params.interval_ticks() {
+            if sim.over || sim.tick().is_err() {
+                break;
+            }
+            ticks += 1;
+        }
+    }
+    sim.players[player].controltype = Controltype::SimpleAI(AiParams::default());
+    while ticks < horizon && !sim.over {
+        if sim.tick().is_err() {
+            break;
+        }
+        ticks += 1;
+    }
+    evaluate(&sim, player)
+}
+
+/// Returns how good the match stands for the player, between 0.0 and 1.0.
+/// Finished matches count as won, lost or drawn, others are judged by the material of both sides
+fn evaluate(sim: &Simulation, player: usize) -> f32 {
+    let team = sim.players[player].team;
+    if sim.over {
+        return match sim.winner {
+            Some(winner) if winner == team => 1.0,
+            Some(_) => 0.0,
+            None => 0.5,
+        };
+    }
+    let material = |player: &Player| {
+        let army: f32 = player.units.iter().map(|unit| {
+            let value = if unit.structure {STRUCTURE_VALUE} else {1.0};
+            value*unit.stats.cost as f32*unit.stats.hp/unit.max_hp
+        }).sum();
+        player.base.hp*BASE_HP_VALUE + army + player.minerals as f32*0.5
+    };
+    let own: f32 = sim.players.iter().filter(|other| other.team == team).map(|other| material(other)).sum();
+    let opposing: f32 = sim.players.iter().filter(|other| other.team != team).map(|other| material(other)).sum();
+    if own + opposing <= 0.0 {
+        0.5
+    } else {
+        own/(own + opposing)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use data::GameData;
+    use setup::Setup;
+
+    fn running_match() -> Simulation {
+        let mut setup = Setup::from_name("aivai").unwrap();
+        setup.set_factions(&["zerg", "terran"]);
+        let mut sim = Simulation::new(&setup, &GameData::bundled(), 3).unwrap();
+        sim.run(10.0).unwrap();
+        sim
+    }
+
+    fn quick() -> MctsParams {
+        MctsParams{
+            iterations: 8,
+            depth: 1,
+            horizon: 5.0,
+            ..MctsParams::default()
+        }
+    }
+
+    #[test]
+    fn search_is_reproducible_without_a_time_limit() {
+        let sim = running_match();
+        let decision = decide(&sim, 0, &quick());
+        assert_eq!(decide(&sim.clone(), 0, &quick()), decision);
+        assert_eq!(decide(&sim, 0, &quick()), decision);
+    }
+
+    #[test]
+    fn candidates_skip_the_current_order() {
+        let mut player = running_match().players.remove(0);
+        player.order = Order::Advance;
+        let actions = candidates(&player);
+        assert!(!actions.contains(&Some(Command::Advance)));
+        assert!(actions.contains(&Some(Command::Hold(DEFENSIVE_POSITION))));
+        player.order = Order::Hold(DEFENSIVE_POSITION);
+        let actions = candidates(&player);
+        assert!(actions.contains(&Some(Command::Advance)));
+        assert!(!actions.contains(&Some(Command::Hold(DEFENSIVE_POSITION))));
+    }
+
+    #[test]
+    fn finished_matches_count_as_won_lost_or_drawn() {
+        let mut sim = running_match();
+        sim.over = true;
+        sim.winner = Some(sim.players[0].team);
+        assert_eq!(evaluate(&sim, 0), 1.0);
+        assert_eq!(evaluate(&sim, 1), 0.0);
+        sim.winner = None;
+        assert_eq!(evaluate(&sim, 0), 0.5);
+    }
+
+    #[test]
+    fn exhausted_time_limit_skips_the_search() {
+        let sim = running_match();
+        let params = MctsParams{
+            iterations: 100_000,
+            time_limit: Some(0),
+            ..quick()
+        };
+        let start = Instant::now();
+        assert_eq!(decide(&sim, 0, &params), None);
+        assert!(start.elapsed() < Duration::from_secs(1));
+    }
+}
@@ -0,0 +1,194 @@
+use traits::Position;
+use gamestate::Side;
+use player::Player;
+use setup::LaneEnd;
+use status::StatusEffect;
+use constants::SimConfig;
+
+
+/// A neutral objective on the line between the bases, as defined in the data file
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ObjectiveDef{
+    pub name: String,
+    /// Position on the map, ranging from 0.0 at the left base to 1.0 at the right base
+    pub position: f32,
+    pub kind: ObjectiveKind,
+}
+
+/// What an objective does, and how it is taken
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "lowercase")]
+pub enum ObjectiveKind{
+    /// Yields `income` additional minerals per tick to the side holding it.
+    /// A side captures the field by being the only one with units within `radius`
+    Minerals{
+        income: u32,
+        radius: f32,
+    },
+    /// A neutral camp that fights units of both sides within `range`.
+    /// The side landing the killing blow inflicts `reward` on all of its units,
+    /// and the camp respawns after `respawn` seconds
+    Camp{
+        hp: f32,
+        damage: f32,
+        range: f32,
+        reward: StatusEffect,
+        respawn: f32,
+    },
+}
+
+
+/// The state of a neutral objective on one lane during a match
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Objective{
+    pub def: ObjectiveDef,
+    pub lane: usize,
+    /// The side of the lane currently holding a mineral field, or the side that killed a camp last
+    pub owner: Option<Side>,
+    /// Remaining hp of a camp, always 0.0 for mineral fields
+    pub hp: f32,
+    /// Time in seconds until a killed camp respawns
+    pub respawn: f32,
+    /// The side that attacked a living camp last, cleared once the camp has been killed
+    last_hit: Option<Side>,
+}
+
+impl Objective {
+    /// Returns a new, unowned `Objective` on the passed lane, as described by the passed definition
+    pub fn new(def: &ObjectiveDef, lane: usize) -> Objective {
+        let hp = match def.kind {
+            ObjectiveKind::Camp{hp, ..} => hp,
+            ObjectiveKind::Minerals{..} => 0.0,
+        };
+        Objective{
+            def: def.clone(),
+            lane: lane,
+            owner: None,
+            hp: hp,
+            respawn: 0.0,
+            last_hit: None,
+        }
+    }
+
+    /// Returns how far the objective is away from the base of `side`, measured like `GameChar::advance()`
+    pub fn advance(&self, side: Side) -> f32 {
+        match side {
+            Side::Left => self.def.position,
+            Side::Right => 1.0 - self.def.position,
+        }
+    }
+
+    /// Returns true if units can attack the objective, which is only the case for living camps
+    pub fn is_attackable(&self) -> bool {
+        match self.def.kind {
+            ObjectiveKind::Camp{..} => self.hp > 0.0,
+            ObjectiveKind::Minerals{..} => false,
+        }
+    }
+
+    /// Deals `damage` to a camp, remembering which side attacked it last
+    pub fn take_damage(&mut self, damage: f32, side: Side){
+        self.hp = (self.hp - damage).max(0.0);
+        self.last_hit = Some(side);
+    }
+
+    /// Returns the additional minerals per tick the objective yields, and the lane end receiving them.
+    /// Every player whose base is at that lane end receives the full income
+    pub fn income(&self) -> Option<(LaneEnd, u32)> {
+        match (self.owner, &self.def.kind) {
+            (Some(side), &ObjectiveKind::Minerals{income, ..}) => Some((LaneEnd{lane: self.lane, side: side}, income)),
+            _ => None,
+        }
+    }
+
+    /// Updates the objective for the units of the passed players on its lane by `delta` seconds.
+    /// Mineral fields change hands, camps fight back, die and respawn.
+    /// Returns the lane end that has just killed a camp, together with its reward
+    pub fn update(&mut self, players: &mut [Player], delta: f32) -> Option<(LaneEnd, StatusEffect)> {
+        let lane = self.lane;
+        match self.def.kind {
+            ObjectiveKind::Minerals{radius, ..} => {
+                let position = self.def.position;
+                let near = |side: Side| players.iter()
+                    .flat_map(|player| &player.units)
+                    .any(|unit| unit.lane() == lane && unit.side() == side && (unit.position()/SimConfig::current().map_scale - position).abs() < radius);
+                // A field stays with its holder until the other side is the only one near it
+                match (near(Side::Left), near(Side::Right)) {
+                    (true, false) => self.owner = Some(Side::Left),
+                    (false, true) => self.owner = Some(Side::Right),
+                    _ => (),
+                }
+                None
+            }
+            ObjectiveKind::Camp{hp, damage, range, ref reward, respawn} => {
+                if self.hp > 0.0 {
+                    // Fight back against the first unit in range
+                    let position = self.position();
+                    let target = players.iter_mut()
+                        .flat_map(|player| &mut player.units)
+                        .find(|unit| unit.lane() == lane && !unit.is_burrowed() && (unit.position() - position).abs() < range);
+                    if let Some(unit) = target {
+                        unit.take_damage(damage*SimConfig::current().damage_scale);
+                    }
+                    None
+                } else if let Some(side) = self.last_hit.take() {
+                    // The camp has just been killed, its reward is only handed out once
+                    self.respawn = respawn;
+                    self.owner = Some(side);
+                    Some((LaneEnd{lane: lane, side: side}, reward.clone()))
+                } else {
+                    self.respawn -= delta;
+                    if self.respawn <= 0.0 {
+                        self.hp = hp;
+                    }
+                    None
+                }
+            }
+        }
+    }
+}
+
+impl Position for Objective {
+    /// Returns the (x-Axis) position of the `Objective`, multiplicated with
+    /// the map scale of the `SimConfig`
+    fn position(&self) -> f32 {
+        self.def.position * SimConfig::current().map_scale
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use data::GameData;
+
+    /// Returns the camp of the bundled data, respawning after the passed seconds
+    fn camp(seconds: f32) -> Objective {
+        let mut def = GameData::bundled().objectives.into_iter().find(|def| def.name == "Creep Camp").unwrap();
+        if let ObjectiveKind::Camp{ref mut respawn, ..} = def.kind {
+            *respawn = seconds;
+        }
+        Objective::new(&def, 0)
+    }
+
+    #[test]
+    fn killed_camp_rewards_once_and_respawns() {
+        let mut camp = camp(1.0);
+        let hp = camp.hp;
+        camp.take_damage(hp, Side::Right);
+        let rewards: Vec<Option<LaneEnd>> = (0..90).map(|_| camp.update(&mut [], 1.0/60.0).map(|(end, _)| end)).collect();
+        assert_eq!(rewards[0], Some(LaneEnd{lane: 0, side: Side::Right}));
+        assert!(rewards[1..].iter().all(|reward| reward.is_none()));
+        assert_eq!(camp.owner, Some(Side::Right));
+        assert_eq!(camp.hp, hp);
+    }
+
+    #[test]
+    fn camp_without_respawn_time_rewards_once() {
+        let mut camp = camp(0.0);
+        let hp = camp.hp;
+        camp.take_damage(hp, Side::Left);
+        let rewards = (0..60).filter(|_| camp.update(&mut [], 1.0/60.0).is_some()).count();
+        assert_eq!(rewards, 1);
+        assert_eq!(camp.hp, hp);
+    }
+}
@@ -1,123 +1,642 @@
 use ggez::{GameResult, GameError};
-use ggez::Context;
 
-use gamestate::Side;
-use gameobject::{GameChar, Base};
+use gameobject::{GameChar, Base, UnitKind, UnitDef, Morph, Morphing, Order};
+use upgrade::Research;
+use data::GameData;
+use faction::Faction;
+use dice::Dice;
+use ability::{AbilityEffect, AbilityState};
+use objective::{Objective, ObjectiveKind};
+use setup::{Slot, LaneEnd};
+use mcts::MctsParams;
+use bot::BotParams;
+use script::ScriptParams;
+use behavior::BehaviorTree;
+use constants::TICKS_PER_SECOND;
 
 /// A player, controlled by either human or AI
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Player{
     pub units: Vec<GameChar>,
     pub minerals: u32,
+    /// Name of the faction the player's roster, upgrades and base come from
+    pub faction: String,
     pub base: Base,
-    controltype: Controltype,
+    /// Players of the same team fight together and win or lose together
+    pub team: usize,
+    /// Index of the lane end in `base.ends` newly spawned units are sent from
+    pub lane: usize,
+    /// The kinds of units the player can spawn
+    pub roster: Vec<UnitDef>,
+    /// Names of all abilities found in the roster, in order of first appearance
+    pub abilities: Vec<String>,
+    pub research: Vec<Research>,
+    pub morphs: Vec<Morph>,
+    /// The order last given to the whole army
+    pub order: Order,
+    /// Position, measured like `GameChar::advance()`, newly spawned units gather at
+    pub rally: Option<f32>,
+    /// Number of units of each kind the player has spawned or morphed so far
+    #[serde(default)]
+    pub built: Vec<(UnitKind, u32)>,
+    /// Color the units and base of the player's faction are tinted with, if any
+    #[serde(default)]
+    pub tint: Option<[f32; 3]>,
+    /// Who decides what the player does: a human or one of the AIs
+    pub controltype: Controltype,
+}
+
+/// An action a `Player` can take, issued either by key press or by an AI
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub enum Command{
+    /// Spawn a unit of the specified kind at the own base
+    Spawn(UnitKind),
+    /// Research the next level of the upgrade at the specified index of `Player.research`
+    Research(usize),
+    /// Morph the rearmost eligible unit using the morph at the specified index of `Player.morphs`
+    Morph(usize),
+    /// Order the whole army to push towards the enemy base
+    Advance,
+    /// Order the whole army to hold at the passed position, measured like `GameChar::advance()`
+    Hold(f32),
+    /// Order the whole army to fall back to the own base
+    Retreat,
+    /// Set or clear the position newly spawned units gather at
+    Rally(Option<f32>),
+    /// Make every unit that is able to use the ability at the specified index of `Player.abilities`
+    Ability(usize),
+    /// Send newly spawned units down the lane at the specified index of `Player.base.ends`
+    Lane(usize),
 }
 
 
 impl Player{
-    /// Returns a new `Player` struct, controlled by a human
-    pub fn new(ctx: &mut Context, side: Side) -> GameResult<Player>{
-        Ok(Player{
-            units: Vec::with_capacity(50),
-            minerals: 0,
-            base: Base::new(ctx, side)?,
-            controltype: Controltype::Human
-        })
+    /// Returns a new `Player` struct for the passed slot, controlled by a human, the controller of the slot or a simple AI
+    pub fn new(slot: &Slot, data: &GameData) -> GameResult<Player>{
+        let controltype = match slot.controller {
+            Some(ref controller) if slot.ai => controller.clone(),
+            _ if slot.ai => Controltype::SimpleAI(AiParams::default()),
+            _ => Controltype::Human,
+        };
+        Ok(Player::with_controltype(slot, data.faction(&slot.faction)?, controltype))
     }
-    /// Returns a new `Player` struct, controlled by a simple AI
-    pub fn new_ai(ctx: &mut Context, side: Side) -> GameResult<Player>{
-        Ok(Player{
+
+    fn with_controltype(slot: &Slot, faction: &Faction, controltype: Controltype) -> Player{
+        let mut abilities = Vec::new();
+        for ability in faction.units.iter().flat_map(|def| &def.abilities) {
+            if !abilities.contains(&ability.name) {
+                abilities.push(ability.name.clone());
+            }
+        }
+        Player{
             units: Vec::with_capacity(50),
             minerals: 0,
-            base: Base::new(ctx, side)?,
-            controltype: Controltype::SimpleAI
-        })
+            faction: faction.name.clone(),
+            base: Base::new(&faction.base, &slot.ends),
+            team: slot.team,
+            lane: 0,
+            roster: faction.units.clone(),
+            abilities: abilities,
+            research: faction.upgrades.iter().cloned().map(Research::new).collect(),
+            morphs: faction.morphs.clone(),
+            order: Order::Advance,
+            rally: None,
+            built: Vec::new(),
+            tint: faction.tint,
+            controltype: controltype,
+        }
     }
-    pub fn build_unit(&mut self, unit: GameChar) -> GameResult<()>{
+
+    /// Executes the passed command, if the player can afford it.
+    /// Newly spawned units are jittered by the passed dice
+    pub fn execute(&mut self, command: Command, dice: &mut Dice) -> GameResult<()>{
+        match command {
+            Command::Spawn(kind) => {
+                let mut unit = {
+                    let def = self.unit_def(kind)?;
+                    if let Some(limit) = def.limit {
+                        if self.count(kind) >= limit {
+                            return Err(GameError::UnknownError(format!("Can't have more than {} {}s", limit, def.name)));
+                        }
+                    }
+                    GameChar::new(def, self.spawn_end())
+                };
+                if !unit.structure {
+                    unit.nudge(dice.jitter());
+                }
+                self.build_unit(unit)
+            }
+            Command::Research(index) => self.research(index),
+            Command::Morph(index) => self.morph(index),
+            Command::Advance => self.give_order(Order::Advance),
+            Command::Hold(position) => self.give_order(Order::Hold(position)),
+            Command::Retreat => self.give_order(Order::Retreat),
+            Command::Rally(rally) => {
+                self.rally = rally;
+                Ok(())
+            }
+            Command::Ability(index) => self.use_ability(index),
+            Command::Lane(index) => {
+                if index >= self.base.ends.len() {
+                    return Err(GameError::UnknownError(format!("No lane with index {}", index)));
+                }
+                self.lane = index;
+                Ok(())
+            }
+        }
+    }
+
+    /// Returns the lane end newly spawned units are sent from
+    pub fn spawn_end(&self) -> LaneEnd {
+        self.base.ends[self.lane]
+    }
+
+    /// Returns false once the player's base has been destroyed
+    pub fn is_alive(&self) -> bool {
+        self.base.hp > 0.0
+    }
+
+    /// Returns the number of units of the passed kind, including those currently morphing into it
+    pub fn count(&self, kind: UnitKind) -> u32 {
+        self.units.iter().filter(|unit| unit.eventual_kind() == kind).count() as u32
+    }
+
+    /// Returns the summed cost of the player's living units and structures
+    pub fn army_value(&self) -> u32 {
+        self.units.iter().map(|unit| unit.stats.cost).sum()
+    }
+
+    /// Returns the summed cost of all units the player has spawned or morphed so far
+    pub fn production_value(&self) -> u32 {
+        self.built.iter().map(|&(kind, count)| self.unit_def(kind).map(|def| def.stats.cost).unwrap_or(0)*count).sum()
+    }
+
+    /// Returns the definition of the passed kind of unit from the player's roster
+    pub fn unit_def(&self, kind: UnitKind) -> GameResult<&UnitDef>{
+        self.roster.iter()
+            .find(|def| def.kind == kind)
+            .ok_or_else(|| GameError::UnknownError(format!("{:?} is not in the roster", kind)))
+    }
+
+    /// Makes every unit that is ready to do so use the ability at the passed index.
+    /// Toggled abilities are turned on for all units that have them off, and only turned off
+    /// once every unit has them on, keeping the army in sync
+    pub fn use_ability(&mut self, index: usize) -> GameResult<()>{
+        let name = match self.abilities.get(index) {
+            Some(name) => name,
+            None => return Err(GameError::UnknownError(format!("No ability with index {}", index))),
+        };
+        let turn_on = self.units.iter()
+            .filter_map(|unit| unit.ability(name))
+            .any(|state| state.is_toggle() && !state.is_active());
+        let mut used = false;
+        for unit in &mut self.units {
+            let in_sync = match unit.ability(name) {
+                Some(state) => state.is_toggle() && state.is_active() == turn_on,
+                None => false,
+            };
+            if !in_sync {
+                used |= unit.use_ability(name);
+            }
+        }
+        if used {
+            Ok(())
+        } else {
+            Err(GameError::UnknownError(format!("No unit is ready to use {}", name)))
+        }
+    }
+
+    /// Gives the passed order to every unit of the army, including those gathered at the rally point
+    pub fn give_order(&mut self, order: Order) -> GameResult<()>{
+        self.order = order;
+        for unit in &mut self.units {
+            unit.order = order;
+        }
+        Ok(())
+    }
+
+    /// Returns the position of the frontmost unit, measured like `GameChar::advance()`,
+    /// or 0.0 if there are no units
+    pub fn front(&self) -> f32 {
+        self.units.iter().map(|unit| unit.advance()).fold(0.0, f32::max)
+    }
+
+    /// Adds the unit to the player's army, applying all researched upgrades to it.
+    /// The new unit walks to the rally point if there is one, and follows the army's order otherwise
+    pub fn build_unit(&mut self, mut unit: GameChar) -> GameResult<()>{
         if unit.stats.cost <= self.minerals {
             self.minerals -= unit.stats.cost;
+            for research in &self.research {
+                research.apply(unit.kind, &mut unit.stats);
+            }
+            unit.order = match self.rally {
+                Some(rally) => Order::Hold(rally),
+                None => self.order,
+            };
+            self.record_built(unit.kind);
             self.units.push(unit);
 
             Ok(())
         }else{
             Err(GameError::IntegerError(String::from("Not enough minerals")))
-        }        
+        }
     }
 
+    /// Researches the next level of the upgrade at the passed index.
+    /// The new level immediately takes effect on all units already on the field
+    pub fn research(&mut self, index: usize) -> GameResult<()>{
+        let research = match self.research.get_mut(index) {
+            Some(research) => research,
+            None => return Err(GameError::UnknownError(format!("No upgrade with index {}", index))),
+        };
+        if research.is_maxed() {
+            return Err(GameError::UnknownError(format!("{} is already fully researched", research.upgrade.name)));
+        }
+        let cost = research.next_cost();
+        if cost > self.minerals {
+            return Err(GameError::IntegerError(String::from("Not enough minerals")));
+        }
+        self.minerals -= cost;
+        research.level += 1;
+        for unit in &mut self.units {
+            if research.upgrade.applies_to(unit.kind) {
+                research.upgrade.apply(&mut unit.stats);
+            }
+        }
+        Ok(())
+    }
 
-    //better: compose with `Controller` struct of AI type, delegate build_decision to composite struct
-    //for AI
-    pub fn build_decision(&self, opponent: &Player){
-        if self.controltype == Controltype::SimpleAI {
 
-            // get these from somewhere else
-            let lingcost = 200;
-            let hydracost = 300;
-            let banecost = 400;
+    /// Starts the morph at the passed index on the rearmost unit that can undergo it,
+    /// keeping the units at the front line fighting
+    pub fn morph(&mut self, index: usize) -> GameResult<()>{
+        let morph = match self.morphs.get(index) {
+            Some(morph) => morph,
+            None => return Err(GameError::UnknownError(format!("No morph with index {}", index))),
+        };
+        if morph.cost > self.minerals {
+            return Err(GameError::IntegerError(String::from("Not enough minerals")));
+        }
+        let rearmost = self.units.iter_mut()
+            .filter(|unit| unit.kind == morph.from && unit.morphing.is_none())
+            .min_by(|a, b| a.advance().partial_cmp(&b.advance()).unwrap());
+        match rearmost {
+            Some(unit) => {
+                self.minerals -= morph.cost;
+                unit.morphing = Some(Morphing{
+                    into: morph.into,
+                    remaining: morph.time,
+                });
+            }
+            None => return Err(GameError::UnknownError(String::from("No unit available to morph"))),
+        }
+        let into = morph.into;
+        self.record_built(into);
+        Ok(())
+    }
 
-            let mut my = Unitcount::new(0,0,0);
-        
-            let mut opposing = Unitcount::new(0,0,0);
+    /// Counts a unit of the passed kind towards `built`
+    fn record_built(&mut self, kind: UnitKind){
+        match self.built.iter_mut().find(|&&mut (k, _)| k == kind) {
+            Some(&mut (_, ref mut n)) => *n += 1,
+            None => self.built.push((kind, 1)),
+        }
+    }
 
-            for unit in &self.units{
-                //better solution: add unit_type enum to the units, match that enum
-                match unit.name.as_str() {
-                    "ling" => my.lingcount += 1,
-                    "hydra" => my.hydracount += 1,
-                    "baneling" => my.banecount += 1,
-                    _ => ()
+    /// Advances morphs, abilities and status effects of all units by `delta` seconds
+    /// and replaces units whose morph has completed
+    pub fn update_units(&mut self, delta: f32) -> GameResult<()>{
+        for i in 0..self.units.len() {
+            self.units[i].update_abilities(delta);
+            self.units[i].update_statuses(delta);
+            let done = match self.units[i].morphing {
+                Some(ref mut morphing) => {
+                    morphing.remaining -= delta;
+                    morphing.remaining <= 0.0
                 }
+                None => false,
+            };
+            if done {
+                let def = self.unit_def(self.units[i].eventual_kind())?;
+                let mut unit = self.units[i].morphed(def);
+                for research in &self.research {
+                    research.apply(unit.kind, &mut unit.stats);
+                }
+                self.units[i] = unit;
+            }
+        }
+        Ok(())
+    }
+
+
+    /// Returns the commands the AI controlling the player wants to execute this tick, rolling the passed dice
+    /// for its random choices. Always returns no commands for human players
+    pub fn build_decision(&self, allies: &[&Player], opponents: &[&Player], objectives: &[Objective], dice: &mut Dice) -> Vec<Command>{
+        match self.controltype {
+            Controltype::Human => Vec::new(),
+            Controltype::SimpleAI(ref params) => self.simple_decision(params, allies, opponents, objectives),
+            Controltype::Random => self.random_decision(dice),
+            Controltype::Scripted{ref build} => self.scripted_decision(build),
+            Controltype::BehaviorTree(ref tree) => tree.decide(self, opponents),
+            // Searching AIs need the whole match, the `Simulation` itself asks them for their plan
+            Controltype::Mcts{plan: Some(kind), ..} => self.scripted_decision(&[kind]),
+            Controltype::Mcts{plan: None, ..} => Vec::new(),
+            Controltype::External(_) | Controltype::Script(_) => Vec::new(),
+        }
+    }
+
+    /// The `SimpleAI` builds to complement the armies of its `allies` against those of all `opponents`
+    fn simple_decision(&self, params: &AiParams, allies: &[&Player], opponents: &[&Player], objectives: &[Objective]) -> Vec<Command>{
+        let mut commands = Vec::new();
+
+        // Send new units down the lane with the most enemy units on it
+        let threat = |end: &LaneEnd| opponents.iter()
+            .flat_map(|player| &player.units)
+            .filter(|unit| unit.lane() == end.lane)
+            .count();
+        let lane = (0..self.base.ends.len())
+            .max_by_key(|&i| (threat(&self.base.ends[i]), i == self.lane))
+            .unwrap_or(self.lane);
+        if lane != self.lane {
+            commands.push(Command::Lane(lane));
+        }
+        let end = self.base.ends[lane];
+
+        let decided = commands.len();
+
+        let own = Unitcount::of(&self.units);
+        let my = Unitcount::of(self.units.iter().chain(allies.iter().flat_map(|player| &player.units)));
+        let opposing = Unitcount::of(opponents.iter().flat_map(|player| &player.units));
+        let opponent_front = opponents.iter().map(|player| player.front()).fold(0.0, f32::max);
+
+        // Hold off a larger army that has already crossed the middle of the map with
+        // static defense, as long as there is little of it yet
+        if let Some(defense) = self.roster.iter().find(|def| def.structure) {
+            if opposing.total() > my.total() && opponent_front > params.defense_front && own.structures < params.max_defense
+                && self.minerals > defense.stats.cost {
+                commands.push(Command::Spawn(defense.kind));
+                return commands;
             }
-            for unit in &opponent.units {
-                //better solution: add unit_type enum to the units, match that enum
-                match unit.name.as_str() {
-                    "ling" => opposing.lingcount += 1,
-                    "hydra" => opposing.hydracount += 1,
-                    "baneling" => opposing.banecount += 1,
-                    _ => ()
+        }
+
+        let mobile: Vec<&UnitDef> = self.roster.iter().filter(|def| !def.structure).collect();
+        if opposing.total() == 0 && mobile.len() >= 2 && self.minerals > mobile[0].stats.cost + mobile[1].stats.cost{
+            commands.push(Command::Spawn(mobile[0].kind));
+            commands.push(Command::Spawn(mobile[1].kind));
+        }
+        // Field more of every kind that is outnumbered by the enemy units it counters
+        let surplus = |kind: UnitKind| match self.unit_def(kind) {
+            Ok(def) => my.count(kind) > opposing.count_of(&def.counters),
+            Err(_) => false,
+        };
+        for def in &mobile {
+            if my.count(def.kind) as f32 >= opposing.count_of(&def.counters) as f32*params.counter_ratio {
+                continue;
+            }
+            // Morphing a surplus unit is cheaper than spawning a fresh one
+            let morph = self.morphs.iter().position(|m| m.into == def.kind && surplus(m.from));
+            match morph {
+                Some(index) if self.minerals > self.morphs[index].cost + params.reserve => {
+                    commands.push(Command::Morph(index));
+                }
+                _ => if self.minerals > def.stats.cost + params.reserve {
+                    commands.push(Command::Spawn(def.kind));
                 }
             }
+        }
 
-            if opposing.total() == 0 && self.minerals > lingcost + hydracost{
-                //build hydra and ling
-            }        
-            if my.lingcount < opposing.hydracount && opposing.hydracount >= opposing.banecount
-                && self.minerals > lingcost {
-                self.build_unit(GameChar::ling(ctx, self.side))
+        // Once the army is large enough compared to the opponent's, invest in upgrades
+        // for the unit kind we field the most instead of building even more units
+        if commands.len() == decided && my.total() > 0 && my.total() as f32 >= opposing.total() as f32*params.research_ratio {
+            if let Some(index) = own.most_common().and_then(|kind| self.upgrade_decision(kind)) {
+                commands.push(Command::Research(index));
             }
-            if my.hydracount < opposing.banecount && opposing.lingcount <= opposing.banecount
-                && self.minerals > hydracost {
-                    //build hydra
+        }
+
+        // Defend in front of the base while heavily outnumbered, push when ahead,
+        // and otherwise try to hold a mineral field for the extra income
+        let field = objectives.iter().find(|objective| objective.lane == end.lane && match objective.def.kind {
+            ObjectiveKind::Minerals{..} => true,
+            _ => false,
+        });
+        let order = if (my.total() as f32) < opposing.total() as f32*params.retreat_ratio {
+            Order::Hold(DEFENSIVE_POSITION)
+        } else if let (Some(field), true) = (field, params.hold_fields && my.total() < opposing.total()) {
+            Order::Hold(field.advance(end.side))
+        } else {
+            Order::Advance
+        };
+        if self.order != order {
+            commands.push(match order {
+                Order::Hold(position) => Command::Hold(position),
+                Order::Retreat => Command::Retreat,
+                Order::Advance => Command::Advance,
+            });
+        }
+
+        // Deploy while holding a position, and pack up again to move on
+        if let Some(index) = self.deploy_ability() {
+            let name = &self.abilities[index];
+            let states: Vec<&AbilityState> = self.units.iter().filter_map(|unit| unit.ability(name)).collect();
+            let toggle = match order {
+                Order::Hold(_) => states.iter().any(|state| !state.is_active()),
+                _ => states.iter().any(|state| state.is_active()),
+            };
+            if toggle {
+                commands.push(Command::Ability(index));
             }
-            if my.banecount < opposing.lingcount &&  opposing.lingcount >= opposing.hydracount
-                && self.minerals > banecost{
-                    //build bane
+        }
+        commands
+    }
+
+    /// The `Random` AI now and then spawns a random affordable unit or structure and gives a random army order
+    fn random_decision(&self, dice: &mut Dice) -> Vec<Command>{
+        let mut commands = Vec::new();
+        if dice.roll() < RANDOM_SPAWN_CHANCE {
+            let affordable: Vec<&UnitDef> = self.roster.iter().filter(|def| def.stats.cost <= self.minerals).collect();
+            if !affordable.is_empty() {
+                let index = ((dice.roll()*affordable.len() as f32) as usize).min(affordable.len() - 1);
+                commands.push(Command::Spawn(affordable[index].kind));
             }
         }
-        
-        
+        if dice.roll() < RANDOM_ORDER_CHANCE {
+            let roll = dice.roll();
+            commands.push(if roll < 0.6 {
+                Command::Advance
+            } else if roll < 0.9 {
+                Command::Hold(self.front())
+            } else {
+                Command::Retreat
+            });
+        }
+        commands
+    }
+
+    /// The `Scripted` AI spawns the units of its build order one after another, starting over at its end
+    fn scripted_decision(&self, build: &[UnitKind]) -> Vec<Command>{
+        let built: u32 = self.built.iter().map(|&(_, n)| n).sum();
+        match build.get(built as usize % build.len().max(1)) {
+            Some(&kind) => match self.unit_def(kind) {
+                Ok(def) if def.stats.cost <= self.minerals => vec!(Command::Spawn(kind)),
+                _ => Vec::new(),
+            },
+            None => Vec::new(),
+        }
+    }
+
+    /// Returns the index into `abilities` of the first ability that deploys units
+    fn deploy_ability(&self) -> Option<usize>{
+        let deploys: Vec<&String> = self.roster.iter()
+            .flat_map(|def| &def.abilities)
+            .filter(|ability| match ability.effect {
+                AbilityEffect::Deploy{..} => true,
+                _ => false,
+            })
+            .map(|ability| &ability.name)
+            .collect();
+        self.abilities.iter().position(|name| deploys.contains(&name))
+    }
+
+    /// Returns the index of the cheapest affordable upgrade that benefits units of the passed kind
+    fn upgrade_decision(&self, kind: UnitKind) -> Option<usize>{
+        self.research.iter()
+            .enumerate()
+            .filter(|&(_, research)| !research.is_maxed() && research.upgrade.applies_to(kind))
+            .filter(|&(_, research)| research.next_cost() <= self.minerals)
+            .min_by_key(|&(_, research)| research.next_cost())
+            .map(|(index, _)| index)
+    }
+}
+
+/// Position, measured like `GameChar::advance()`, the simple AI holds at while outnumbered
+const DEFENSIVE_POSITION: f32 = 0.15;
+
+/// Chance per tick of the `Random` AI to spawn a unit
+const RANDOM_SPAWN_CHANCE: f32 = 0.05;
+
+/// Chance per tick of the `Random` AI to give a new army order
+const RANDOM_ORDER_CHANCE: f32 = 0.005;
+
+/// Who decides what a `Player` does
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum Controltype{
+    /// Commands come from key presses
+    Human,
+    /// Counters the opposing army, with thresholds tuned by its params
+    #[serde(rename = "simple_ai")]
+    SimpleAI(AiParams),
+    /// Spawns random units and gives random army orders
+    Random,
+    /// Spawns the units of `build` one after another, over and over
+    Scripted{
+        build: Vec<UnitKind>,
+    },
+    /// Searches for the best next decisions by playing the match forward, see `mcts::decide`
+    Mcts{
+        #[serde(default)]
+        params: MctsParams,
+        /// Kind of unit the last search decided to spawn whenever affordable, until the next search
+        #[serde(default)]
+        plan: Option<UnitKind>,
+    },
+    /// An external program, see `bot::Bot`
+    External(BotParams),
+    /// A Rhai script, reloaded whenever it changes, see `script::Script`. Unlike `Scripted`, it decides on its own
+    Script(ScriptParams),
+    /// Follows a behavior tree, see `behavior::BehaviorTree`
+    BehaviorTree(BehaviorTree),
+}
+
+/// Tunable thresholds of the `SimpleAI`. The defaults are the ones it has always played with
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct AiParams{
+    /// Falls back in front of the base while the own army is smaller than this share of the opposing one
+    pub retreat_ratio: f32,
+    /// Builds static defense against a larger army once its front has advanced this far, measured like `GameChar::advance()`
+    pub defense_front: f32,
+    /// Maximum number of structures built for defense
+    pub max_defense: u32,
+    /// Researches upgrades once the own army is at least this share of the opposing one
+    pub research_ratio: f32,
+    /// Holds the mineral field of its lane while behind, instead of advancing
+    pub hold_fields: bool,
+    /// Fields more of a kind while it is outnumbered by this many times the enemy units it counters
+    pub counter_ratio: f32,
+    /// Minerals kept in reserve when spawning and morphing units
+    pub reserve: u32,
+    /// Seconds between two decisions
+    pub reaction: f32,
+}
+
+impl Default for AiParams {
+    fn default() -> AiParams {
+        AiParams{
+            retreat_ratio: 0.5,
+            defense_front: 0.5,
+            max_defense: 2,
+            research_ratio: 1.0,
+            hold_fields: true,
+            counter_ratio: 1.0,
+            reserve: 0,
+            reaction: 0.0,
+        }
     }
 }
 
-#[derive(Debug, Clone, Copy, PartialEq)]
-enum Controltype{
-    Human, SimpleAI,
+impl AiParams {
+    /// Returns the number of ticks between two decisions
+    pub fn reaction_ticks(&self) -> u64 {
+        ((self.reaction*TICKS_PER_SECOND as f32) as u64).max(1)
+    }
 }
 
+/// The number of units of each kind in an army, counting morphing units as what they will become
 struct Unitcount{
-    lingcount: u32,
-    hydracount: u32,
-    banecount: u32
+    /// Mobile units per kind, in order of first appearance
+    counts: Vec<(UnitKind, u32)>,
+    structures: u32,
 }
 impl Unitcount{
-    fn new(lingcount: u32, hydracount: u32, banecount: u32) -> Unitcount{
-        Unitcount{
-            lingcount,
-            hydracount,
-            banecount
+    /// Counts the units of each kind in `units`
+    fn of<'a, I: IntoIterator<Item = &'a GameChar>>(units: I) -> Unitcount{
+        let mut count = Unitcount{
+            counts: Vec::new(),
+            structures: 0,
+        };
+        for unit in units{
+            if unit.structure {
+                count.structures += 1;
+                continue;
+            }
+            let kind = unit.eventual_kind();
+            match count.counts.iter_mut().find(|&&mut (k, _)| k == kind) {
+                Some(&mut (_, ref mut n)) => *n += 1,
+                None => count.counts.push((kind, 1)),
+            }
         }
+        count
     }
+    /// Returns the number of units of the passed kind
+    fn count(&self, kind: UnitKind) -> u32{
+        self.counts.iter().find(|&&(k, _)| k == kind).map(|&(_, n)| n).unwrap_or(0)
+    }
+    /// Returns the number of units of any of the passed kinds
+    fn count_of(&self, kinds: &[UnitKind]) -> u32{
+        kinds.iter().map(|&kind| self.count(kind)).sum()
+    }
+    /// Returns the number of mobile units, not counting structures
     fn total(&self) -> u32{
-        self.lingcount + self.hydracount + self.banecount
+        self.counts.iter().map(|&(_, n)| n).sum()
+    }
+    /// Returns the kind with the highest count, preferring the kind that appeared first on ties
+    fn most_common(&self) -> Option<UnitKind>{
+        self.counts.iter()
+            .fold(None, |best: Option<(UnitKind, u32)>, &(kind, n)| match best {
+                Some((_, m)) if m >= n => best,
+                _ => Some((kind, n)),
+            })
+            .map(|(kind, _)| kind)
     }
 }
@@ -0,0 +1,239 @@
+use ggez::GameError;
+use numpy::{PyArray1, IntoPyArray};
+use pyo3::prelude::*;
+use pyo3::exceptions::{PyIndexError, PyRuntimeError, PyValueError};
+use pyo3::types::PyDict;
+use serde_json;
+
+use std::fs::File;
+
+use player::{Player, Command, Controltype};
+use gameobject::UnitKind;
+use data::GameData;
+use setup::Setup;
+use simulation::Simulation;
+use gym::{Env, Action, Observation};
+
+
+/// Python exception for errors of the simulation
+fn error(e: GameError) -> PyErr {
+    PyRuntimeError::new_err(e.to_string())
+}
+
+/// Loads the game data from the json file at the passed path
+fn load(path: &str) -> PyResult<GameData> {
+    let file = File::open(path).map_err(|e| PyRuntimeError::new_err(format!("could not open {}: {}", path, e)))?;
+    GameData::from_reader(file).map_err(error)
+}
+
+/// Parses json the way the data files are written, e.g. `"ling"` or `{"type": "simple_ai"}`
+fn parse<T: ::serde::de::DeserializeOwned>(json: &str) -> PyResult<T> {
+    serde_json::from_str(json).map_err(|e| PyValueError::new_err(format!("invalid {}: {}", json, e)))
+}
+
+/// Parses a unit kind named like in the data files, e.g. `ling`
+fn unit_kind(name: &str) -> PyResult<UnitKind> {
+    parse(&format!("\"{}\"", name))
+}
+
+/// A headless match. Players start out as set up by the mode, human players only act on the commands passed to them
+#[pyclass(name = "Match")]
+pub struct PyMatch{
+    sim: Simulation,
+}
+
+impl PyMatch {
+    fn player(&mut self, player: usize) -> PyResult<&mut Player> {
+        self.sim.players.get_mut(player).ok_or_else(|| PyIndexError::new_err(format!("No player {}", player)))
+    }
+
+    /// Executes the command for the passed player, returning whether it could be executed
+    fn execute(&mut self, player: usize, command: Command) -> PyResult<bool> {
+        let over = self.sim.over;
+        let Simulation{ref mut players, ref mut dice, ..} = self.sim;
+        let player = players.get_mut(player).ok_or_else(|| PyIndexError::new_err(format!("No player {}", player)))?;
+        Ok(!over && player.is_alive() && player.execute(command, dice).is_ok())
+    }
+}
+
+#[pymethods]
+impl PyMatch {
+    /// Starts a match of the passed mode, e.g. `1v1` or `ffa3`, whose randomness derives from `seed`
+    #[new]
+    #[pyo3(signature = (mode = "1v1", factions = None, seed = 0, data = "assets/gamedata.json"))]
+    fn new(mode: &str, factions: Option<Vec<String>>, seed: u64, data: &str) -> PyResult<PyMatch> {
+        let mut setup = Setup::from_name(mode).ok_or_else(|| PyValueError::new_err(format!("Unknown mode {}", mode)))?;
+        if let Some(factions) = factions {
+            let factions: Vec<&str> = factions.iter().map(|faction| faction.as_str()).collect();
+            setup.set_factions(&factions);
+        }
+        let sim = Simulation::new(&setup, &load(data)?, seed).map_err(error)?;
+        Ok(PyMatch{
+            sim: sim,
+        })
+    }
+
+    /// Hands the player to a controller, given as json like in `entrants.json`, e.g. `{"type": "simple_ai"}`
+    fn set_controller(&mut self, player: usize, controller: &str) -> PyResult<()> {
+        let controller: Controltype = parse(controller)?;
+        self.player(player)?.controltype = controller;
+        Ok(())
+    }
+
+    /// Spawns a unit of the passed kind for the player, returning whether it could afford it
+    fn spawn(&mut self, player: usize, kind: &str) -> PyResult<bool> {
+        let kind = unit_kind(kind)?;
+        self.execute(player, Command::Spawn(kind))
+    }
+
+    /// Orders the player's army to push towards the enemy base
+    fn advance(&mut self, player: usize) -> PyResult<bool> {
+        self.execute(player, Command::Advance)
+    }
+
+    /// Orders the player's army to hold at the passed position, from 0.0 at its base to 1.0 at the enemy base
+    fn hold(&mut self, player: usize, position: f32) -> PyResult<bool> {
+        self.execute(player, Command::Hold(position))
+    }
+
+    /// Orders the player's army to fall back to its base
+    fn retreat(&mut self, player: usize) -> PyResult<bool> {
+        self.execute(player, Command::Retreat)
+    }
+
+    /// Advances the match by the passed number of ticks, 60 per second, or until it is over
+    #[pyo3(signature = (ticks = 1))]
+    fn tick(&mut self, ticks: u64) -> PyResult<()> {
+        for _ in 0..ticks {
+            if self.sim.over {
+                break;
+            }
+            self.sim.tick().map_err(error)?;
+        }
+        Ok(())
+    }
+
+    /// Runs the match until it is over or has lasted `seconds` since its start
+    fn run(&mut self, seconds: f32) -> PyResult<()> {
+        self.sim.run(seconds).map_err(error)
+    }
+
+    /// Whether at most one team is left standing
+    #[getter]
+    fn over(&self) -> bool {
+        self.sim.over
+    }
+
+    /// The team left standing once the match is over, `None` on a draw
+    #[getter]
+    fn winner(&self) -> Option<usize> {
+        self.sim.winner
+    }
+
+    /// Number of ticks since the start of the match
+    #[getter]
+    fn ticks(&self) -> u64 {
+        self.sim.tick
+    }
+
+    /// Number of players
+    #[getter]
+    fn players(&self) -> usize {
+        self.sim.players.len()
+    }
+
+    fn team(&mut self, player: usize) -> PyResult<usize> {
+        Ok(self.player(player)?.team)
+    }
+
+    fn minerals(&mut self, player: usize) -> PyResult<u32> {
+        Ok(self.player(player)?.minerals)
+    }
+
+    fn base_hp(&mut self, player: usize) -> PyResult<f32> {
+        Ok(self.player(player)?.base.hp)
+    }
+
+    /// Returns the kind, position and hp of every unit of the player,
+    /// the position ranging from 0.0 at its base to 1.0 at the enemy base
+    fn units(&mut self, player: usize) -> PyResult<Vec<(String, f32, f32)>> {
+        Ok(self.player(player)?.units.iter().map(|unit| {
+            let kind = serde_json::to_value(unit.kind).ok().and_then(|kind| kind.as_str().map(String::from)).unwrap_or_default();
+            (kind, unit.advance(), unit.stats.hp)
+        }).collect())
+    }
+
+    /// Returns the complete state of the match as json, in the format of save games
+    fn state(&self) -> PyResult<String> {
+        serde_json::to_string(&self.sim.save()).map_err(|e| PyRuntimeError::new_err(e.to_string()))
+    }
+}
+
+/// Observation, reward, whether the episode is done and details of a step
+type Step<'py> = (Bound<'py, PyArray1<f32>>, f32, bool, Bound<'py, PyDict>);
+
+/// The reinforcement learning environment, see `gym::Env`. Observations are numpy arrays of float32
+#[pyclass(name = "Env")]
+pub struct PyEnv{
+    env: Env,
+}
+
+#[pymethods]
+impl PyEnv {
+    /// Returns a new environment, the opponent given as json like in `entrants.json` and the `SimpleAI` if `None`
+    #[new]
+    #[pyo3(signature = (data = "assets/gamedata.json", ticks_per_step = 30, timeout = 600.0, opponent = None, opponent_faction = "zerg"))]
+    fn new(data: &str, ticks_per_step: u64, timeout: f32, opponent: Option<&str>, opponent_faction: &str) -> PyResult<PyEnv> {
+        let mut env = Env::new(load(data)?);
+        env.ticks_per_step = ticks_per_step;
+        env.timeout = timeout;
+        if let Some(opponent) = opponent {
+            env.opponent = parse(opponent)?;
+        }
+        env.opponent_faction = opponent_faction.to_string();
+        Ok(PyEnv{
+            env: env,
+        })
+    }
+
+    /// Starts a new episode, returning the first observation
+    #[pyo3(signature = (seed = 0))]
+    fn reset<'py>(&mut self, py: Python<'py>, seed: u64) -> PyResult<Bound<'py, PyArray1<f32>>> {
+        let observation = self.env.reset(seed).map_err(error)?;
+        Ok(observation.values.into_pyarray(py))
+    }
+
+    /// Takes the action with the passed index, returning the observation, the reward, whether the episode is done
+    /// and a dict with the `tick`, whether the action was `executed`, the `winner` and whether it ended by `timeout`
+    fn step<'py>(&mut self, py: Python<'py>, action: usize) -> PyResult<Step<'py>> {
+        let action = Action::from_index(action)
+            .ok_or_else(|| PyValueError::new_err(format!("Actions range from 0 to {}", Action::COUNT - 1)))?;
+        let (observation, reward, done, info) = self.env.step(action).map_err(error)?;
+        let dict = PyDict::new(py);
+        dict.set_item("tick", info.tick)?;
+        dict.set_item("executed", info.executed)?;
+        dict.set_item("winner", info.winner)?;
+        dict.set_item("timeout", info.timeout)?;
+        Ok((observation.values.into_pyarray(py), reward, done, dict))
+    }
+
+    /// Number of values of every observation
+    #[getter]
+    fn observation_size(&self) -> usize {
+        Observation::LEN
+    }
+
+    /// Number of actions, `step` takes their index
+    #[getter]
+    fn action_count(&self) -> usize {
+        Action::COUNT
+    }
+}
+
+/// The `zerg_warz` Python module
+#[pymodule]
+fn zerg_warz(m: &Bound<'_, PyModule>) -> PyResult<()> {
+    m.add_class::<PyMatch>()?;
+    m.add_class::<PyEnv>()?;
+    Ok(())
+}
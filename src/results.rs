@@ -0,0 +1,32 @@
+use ggez::{GameResult, GameError};
+use serde_json;
+
+use std::fs::OpenOptions;
+use std::io::Write;
+
+
+/// The outcome of a finished match, together with everything needed to reproduce it
+#[derive(Debug, Clone, Serialize)]
+pub struct MatchResult{
+    /// Seed of the match's `Dice`
+    pub seed: u64,
+    /// Number of simulation ticks the match lasted
+    pub ticks: u64,
+    /// The winning team, `None` on a draw
+    pub winner: Option<usize>,
+    /// Factions of all players, in order
+    pub factions: Vec<String>,
+    /// Name of the scenario played, if any
+    pub scenario: Option<String>,
+}
+
+impl MatchResult {
+    /// Appends the result as a single line of json to the file at the passed path
+    pub fn append(&self, path: &str) -> GameResult<()> {
+        let line = serde_json::to_string(self)
+            .map_err(|e| GameError::UnknownError(format!("could not serialize result: {}", e)))?;
+        let mut file = OpenOptions::new().create(true).append(true).open(path)?;
+        writeln!(file, "{}", line)?;
+        Ok(())
+    }
+}
@@ -0,0 +1,80 @@
+use ggez::{GameResult, GameError};
+use serde_json;
+
+use std::fs::File;
+
+use player::Player;
+use objective::Objective;
+use dice::Dice;
+use scenario::Scenario;
+
+
+/// The complete state of a match, without anything that is only needed to draw it,
+/// so it can be written to a file and resumed later
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SaveGame{
+    pub lanes: usize,
+    pub players: Vec<Player>,
+    /// Indices into `players` of the human players, in the order of their key bindings
+    pub humans: Vec<usize>,
+    pub objectives: Vec<Objective>,
+    pub over: bool,
+    pub winner: Option<usize>,
+    pub dice: Dice,
+    pub tick: u64,
+    /// The scenario being played, if any
+    #[serde(default)]
+    pub scenario: Option<Scenario>,
+}
+
+impl SaveGame {
+    /// Writes the save game as json to the file at the passed path
+    pub fn write(&self, path: &str) -> GameResult<()> {
+        let file = File::create(path)?;
+        serde_json::to_writer_pretty(file, self)
+            .map_err(|e| GameError::UnknownError(format!("could not write save game: {}", e)))
+    }
+
+    /// Reads a save game from the json file at the passed path
+    pub fn read(path: &str) -> GameResult<SaveGame> {
+        let file = File::open(path)?;
+        serde_json::from_reader(file)
+            .map_err(|e| GameError::ResourceLoadError(format!("invalid save game: {}", e)))
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use data::GameData;
+    use setup::Setup;
+    use simulation::Simulation;
+
+    fn running_match() -> Simulation {
+        let data = GameData::bundled();
+        let mut setup = Setup::from_name("aivai").unwrap();
+        setup.set_factions(&["zerg", "terran"]);
+        let mut sim = Simulation::new(&setup, &data, 7).unwrap();
+        sim.run(20.0).unwrap();
+        sim
+    }
+
+    #[test]
+    fn json_round_trip_keeps_the_match() {
+        let save = running_match().save();
+        let json = serde_json::to_string(&save).unwrap();
+        let read: SaveGame = serde_json::from_str(&json).unwrap();
+        assert_eq!(serde_json::to_string(&read).unwrap(), json);
+    }
+
+    #[test]
+    fn resumed_match_plays_on_like_the_original() {
+        let mut original = running_match();
+        let json = serde_json::to_string(&original.save()).unwrap();
+        let mut resumed = Simulation::from_save(serde_json::from_str(&json).unwrap());
+        original.run(40.0).unwrap();
+        resumed.run(40.0).unwrap();
+        assert_eq!(serde_json::to_string(&resumed.save()).unwrap(), serde_json::to_string(&original.save()).unwrap());
+    }
+}
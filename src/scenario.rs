@@ -0,0 +1,238 @@
+use ggez::{GameResult, GameError};
+use serde_json;
+
+use std::fs::File;
+
+use gamestate::Side;
+use gameobject::{GameChar, UnitKind, Order};
+use player::Player;
+use data::GameData;
+use dice::Dice;
+use save::SaveGame;
+use setup::{Slot, LaneEnd};
+use constants::TICKS_PER_SECOND;
+
+
+/// A hand-authored starting position on a single lane, together with a win condition,
+/// used to test how a combat situation plays out
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Scenario{
+    pub name: String,
+    #[serde(default)]
+    pub description: String,
+    pub players: Vec<ScenarioPlayer>,
+    pub win: WinCondition,
+    /// Seed of the match's `Dice`. The seed passed on the command line is used if there is none
+    #[serde(default)]
+    pub seed: Option<u64>,
+}
+
+/// A player of a `Scenario` with the units it starts with
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ScenarioPlayer{
+    pub faction: String,
+    pub side: Side,
+    /// Defaults to a team of its own
+    #[serde(default)]
+    pub team: Option<usize>,
+    #[serde(default)]
+    pub minerals: u32,
+    /// Defaults to the hp of the faction's base
+    #[serde(default)]
+    pub base_hp: Option<f32>,
+    #[serde(default)]
+    pub ai: bool,
+    #[serde(default)]
+    pub units: Vec<ScenarioUnits>,
+}
+
+/// A group of units of the same kind a `ScenarioPlayer` starts with
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ScenarioUnits{
+    pub kind: UnitKind,
+    #[serde(default = "one")]
+    pub count: u32,
+    /// Distance from the own base, measured like `GameChar::advance()`
+    #[serde(default)]
+    pub position: f32,
+    /// Makes the units hold their position instead of advancing
+    #[serde(default)]
+    pub hold: bool,
+}
+
+fn one() -> u32 {
+    1
+}
+
+/// What the player at index `player` has to achieve within `seconds` for the scenario to pass
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum WinCondition{
+    /// Keep the own base standing
+    Survive{
+        #[serde(default)]
+        player: usize,
+        seconds: f32,
+    },
+    /// Destroy all enemy bases
+    DestroyBase{
+        #[serde(default)]
+        player: usize,
+        seconds: f32,
+    },
+    /// Kill all enemy units
+    Eliminate{
+        #[serde(default)]
+        player: usize,
+        seconds: f32,
+    },
+}
+
+impl Scenario {
+    /// Reads a scenario from the json file at the passed path
+    pub fn read(path: &str) -> GameResult<Scenario> {
+        let file = File::open(path)?;
+        let scenario: Scenario = serde_json::from_reader(file)
+            .map_err(|e| GameError::ResourceLoadError(format!("invalid scenario: {}", e)))?;
+        scenario.validate()?;
+        Ok(scenario)
+    }
+
+    /// Returns an error if the win condition refers to a player the scenario doesn't have
+    fn validate(&self) -> GameResult<()> {
+        if self.win.player() >= self.players.len() {
+            return Err(GameError::ResourceLoadError(format!("invalid scenario: the win condition refers to player {}, but there are only {} players",
+                self.win.player(), self.players.len())));
+        }
+        Ok(())
+    }
+
+    /// Returns the starting position of the scenario as a match that can be resumed.
+    /// `seed` is only used if the scenario doesn't specify its own
+    pub fn start(&self, data: &GameData, seed: u64) -> GameResult<SaveGame> {
+        self.validate()?;
+        let mut players = Vec::with_capacity(self.players.len());
+        for (i, spec) in self.players.iter().enumerate() {
+            let end = LaneEnd{lane: 0, side: spec.side};
+            let slot = Slot{
+                team: spec.team.unwrap_or(i),
+                ends: vec!(end),
+                ai: spec.ai,
+                faction: spec.faction.clone(),
+                controller: None,
+            };
+            let invalid = |e: GameError| GameError::ResourceLoadError(format!("invalid scenario: player {}: {}", i, e));
+            let mut player = Player::new(&slot, data).map_err(&invalid)?;
+            player.minerals = spec.minerals;
+            if let Some(hp) = spec.base_hp {
+                player.base.hp = hp;
+            }
+            for group in &spec.units {
+                let def = player.unit_def(group.kind).map_err(&invalid)?.clone();
+                for _ in 0..group.count {
+                    let mut unit = GameChar::new(&def, end);
+                    unit.nudge(group.position);
+                    if group.hold {
+                        unit.order = Order::Hold(group.position);
+                    }
+                    player.units.push(unit);
+                }
+            }
+            players.push(player);
+        }
+        Ok(SaveGame{
+            lanes: 1,
+            players: players,
+            humans: self.players.iter().enumerate().filter(|&(_, spec)| !spec.ai).map(|(i, _)| i).collect(),
+            objectives: Vec::new(),
+            over: false,
+            winner: None,
+            dice: Dice::new(self.seed.unwrap_or(seed), data.randomness),
+            tick: 0,
+            scenario: Some(self.clone()),
+        })
+    }
+}
+
+impl WinCondition {
+    /// Returns the index of the player that has to fulfil the condition
+    pub fn player(&self) -> usize {
+        match *self {
+            WinCondition::Survive{player, ..} | WinCondition::DestroyBase{player, ..} | WinCondition::Eliminate{player, ..} => player,
+        }
+    }
+
+    /// Returns whether the condition has been fulfilled after `tick` ticks,
+    /// or `None` if that's not decided yet
+    pub fn check(&self, players: &[Player], tick: u64) -> Option<bool> {
+        let me = &players[self.player()];
+        let enemies: Vec<&Player> = players.iter().filter(|player| player.team != me.team).collect();
+        let bases_destroyed = enemies.iter().all(|player| !player.is_alive());
+        let army = |player: &Player| player.units.iter().any(|unit| unit.stats.hp > 0.0);
+        let elapsed = tick as f32/TICKS_PER_SECOND as f32;
+        match *self {
+            WinCondition::Survive{seconds, ..} => if !me.is_alive() {
+                Some(false)
+            } else if elapsed >= seconds || bases_destroyed {
+                Some(true)
+            } else {
+                None
+            },
+            WinCondition::DestroyBase{seconds, ..} => if bases_destroyed {
+                Some(true)
+            } else if !me.is_alive() || elapsed >= seconds {
+                Some(false)
+            } else {
+                None
+            },
+            WinCondition::Eliminate{seconds, ..} => if !enemies.iter().any(|player| army(player)) {
+                Some(true)
+            } else if !army(me) || elapsed >= seconds {
+                Some(false)
+            } else {
+                None
+            },
+        }
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+
+    fn scenario(json: &str) -> Scenario {
+        serde_json::from_str(json).unwrap()
+    }
+
+    #[test]
+    fn shipped_scenarios_start() {
+        let data = GameData::bundled();
+        for entry in fs::read_dir(concat!(env!("CARGO_MANIFEST_DIR"), "/scenarios")).unwrap() {
+            let path = entry.unwrap().path();
+            let scenario = Scenario::read(path.to_str().unwrap()).unwrap();
+            assert!(scenario.start(&data, 1).is_ok(), "{:?} doesn't start", path);
+        }
+    }
+
+    #[test]
+    fn win_condition_of_a_missing_player_is_rejected() {
+        let scenario = scenario(r#"{
+            "name": "Nobody",
+            "players": [{"faction": "zerg", "side": "left"}, {"faction": "zerg", "side": "right"}],
+            "win": {"type": "survive", "player": 5, "seconds": 10.0}
+        }"#);
+        assert!(scenario.start(&GameData::bundled(), 1).is_err());
+    }
+
+    #[test]
+    fn units_outside_the_faction_are_rejected() {
+        let scenario = scenario(r#"{
+            "name": "Marines",
+            "players": [{"faction": "zerg", "side": "left", "units": [{"kind": "marine"}]}, {"faction": "terran", "side": "right"}],
+            "win": {"type": "survive", "seconds": 10.0}
+        }"#);
+        assert!(scenario.start(&GameData::bundled(), 1).is_err());
+    }
+}
@@ -0,0 +1,264 @@
+use ggez::{GameResult, GameError};
+use rhai::{Engine, AST, Array, Dynamic, Scope};
+use rhai::serde::{to_dynamic, from_dynamic};
+
+use std::collections::HashMap;
+use std::fs;
+use std::time::SystemTime;
+
+use player::{Player, Command};
+use gameobject::{UnitKind, Order};
+use bot::Reply;
+use constants::TICKS_PER_SECOND;
+
+
+/// Operations a script may run per decision, so a script stuck in a loop can't freeze the match
+const MAX_OPERATIONS: u64 = 1_000_000;
+
+/// Which script controls a player, and how often it is asked for its commands
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ScriptParams{
+    /// Path of the Rhai script, which has to define `fn decide(observation)`
+    pub path: String,
+    /// Seconds between two decisions
+    #[serde(default = "default_interval")]
+    pub interval: f32,
+}
+
+fn default_interval() -> f32 {
+    0.5
+}
+
+impl ScriptParams {
+    /// Returns the number of ticks between two decisions
+    pub fn interval_ticks(&self) -> u64 {
+        ((self.interval*TICKS_PER_SECOND as f32) as u64).max(1)
+    }
+}
+
+/// What a script gets to see of the match, passed to its `decide` function as an object map
+#[derive(Serialize)]
+struct Observation<'a>{
+    tick: u64,
+    minerals: u32,
+    base_hp: f32,
+    /// Base hp of the weakest opponent still standing
+    enemy_base_hp: f32,
+    /// `advance`, `hold` or `retreat`
+    order: &'static str,
+    /// Units per kind of the player, counting morphing units as what they will become
+    own: HashMap<UnitKind, u32>,
+    /// Units per kind of all opponents together
+    enemy: HashMap<UnitKind, u32>,
+    roster: Vec<RosterEntry<'a>>,
+}
+
+#[derive(Serialize)]
+struct RosterEntry<'a>{
+    kind: UnitKind,
+    cost: u32,
+    structure: bool,
+    counters: &'a [UnitKind],
+}
+
+/// A Rhai script controlling a player. The script is compiled again whenever its file changes,
+/// so it can be edited while the match is running
+#[derive(Debug)]
+pub struct Script{
+    engine: Engine,
+    ast: AST,
+    path: String,
+    /// When the file was last changed, as of the last compilation
+    modified: Option<SystemTime>,
+    /// Whether the last decision failed, so the same error isn't printed over and over
+    failed: bool,
+}
+
+impl Script {
+    /// Compiles the script at the passed path
+    pub fn load(path: &str) -> GameResult<Script> {
+        let mut engine = Engine::new();
+        engine.set_max_operations(MAX_OPERATIONS);
+        let ast = compile(&engine, path)?;
+        Ok(Script{
+            engine: engine,
+            ast: ast,
+            path: path.to_string(),
+            modified: modified(path),
+            failed: false,
+        })
+    }
+
+    /// Compiles the script again if its file has changed. Scripts that fail to compile keep running their last version
+    fn reload(&mut self) {
+        let modified = modified(&self.path);
+        if modified == self.modified {
+            return;
+        }
+        self.modified = modified;
+        match compile(&self.engine, &self.path) {
+            Ok(ast) => {
+                println!("Reloaded {}", self.path);
+                self.ast = ast;
+                self.failed = false;
+            }
+            Err(e) => println!("{}", e),
+        }
+    }
+
+    /// Returns the commands the script decides on for the player at the passed index.
+    /// A script that fails prints why and doesn't issue any commands until it works again
+    pub fn decide(&mut self, players: &[Player], player: usize, tick: u64) -> Vec<Command> {
+        self.reload();
+        match self.call(players, player, tick) {
+            Ok(commands) => {
+                self.failed = false;
+                commands
+            }
+            Err(e) => {
+                if !self.failed {
+                    println!("Script {} failed: {}", self.path, e);
+                    self.failed = true;
+                }
+                Vec::new()
+            }
+        }
+    }
+
+    /// Calls the `decide` function of the script, which returns an array of commands.
+    /// A command is either the kind of unit to spawn, e.g. `"ling"`, or an object map like the commands of bots,
+    /// e.g. `#{type: "hold", position: 0.3}`
+    fn call(&self, players: &[Player], player: usize, tick: u64) -> GameResult<Vec<Command>> {
+        let me = &players[player];
+        let opponents = || players.iter().filter(|other| other.team != me.team && other.is_alive());
+        let weakest = opponents().map(|other| other.base.hp).fold(f32::INFINITY, f32::min);
+        // Every kind any player can field is counted, so scripts can look up kinds nobody has yet
+        let kinds: Vec<UnitKind> = players.iter().flat_map(|other| other.roster.iter().map(|def| def.kind)).collect();
+        let observation = Observation{
+            tick: tick,
+            minerals: me.minerals,
+            base_hp: me.base.hp,
+            enemy_base_hp: if weakest.is_finite() {weakest} else {0.0},
+            order: match me.order {
+                Order::Advance => "advance",
+                Order::Hold(_) => "hold",
+                Order::Retreat => "retreat",
+            },
+            own: count(&kinds, me.units.iter().map(|unit| unit.eventual_kind())),
+            enemy: count(&kinds, opponents().flat_map(|other| other.units.iter().map(|unit| unit.eventual_kind()))),
+            roster: me.roster.iter().map(|def| RosterEntry{
+                kind: def.kind,
+                cost: def.stats.cost,
+                structure: def.structure,
+                counters: &def.counters,
+            }).collect(),
+        };
+        let observation = to_dynamic(&observation).map_err(|e| GameError::UnknownError(e.to_string()))?;
+        let result: Dynamic = self.engine.call_fn(&mut Scope::new(), &self.ast, "decide", (observation,))
+            .map_err(|e| GameError::UnknownError(e.to_string()))?;
+        let commands = result.try_cast::<Array>()
+            .ok_or_else(|| GameError::UnknownError(String::from("decide has to return an array of commands")))?;
+        commands.iter().map(|command| {
+            let command = if command.is_string() {
+                from_dynamic::<UnitKind>(command).map(Command::Spawn)
+            } else {
+                from_dynamic::<Reply>(command).map(|reply| reply.command())
+            };
+            command.map_err(|e| GameError::UnknownError(format!("invalid command: {}", e)))
+        }).collect()
+    }
+}
+
+/// Returns the number of units of each of the passed kinds
+fn count<I: Iterator<Item = UnitKind>>(kinds: &[UnitKind], units: I) -> HashMap<UnitKind, u32> {
+    let mut counts: HashMap<UnitKind, u32> = kinds.iter().map(|&kind| (kind, 0)).collect();
+    for kind in units {
+        *counts.entry(kind).or_insert(0) += 1;
+    }
+    counts
+}
+
+fn compile(engine: &Engine, path: &str) -> GameResult<AST> {
+    engine.compile_file(path.into()).map_err(|e| GameError::UnknownError(format!("could not compile {}: {}", path, e)))
+}
+
+/// Returns when the file at the passed path was last changed
+fn modified(path: &str) -> Option<SystemTime> {
+    fs::metadata(path).and_then(|metadata| metadata.modified()).ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use data::GameData;
+    use player::Controltype;
+    use setup::Setup;
+    use simulation::Simulation;
+    use std::env;
+    use std::fs::File;
+    use std::process;
+    use std::time::{Duration, UNIX_EPOCH};
+
+    /// Writes the script to a temporary file named after the test, changed at the passed second,
+    /// so each version is seen as a change however fast the test rewrites it
+    fn write(name: &str, source: &str, second: u64) -> String {
+        let path = env::temp_dir().join(format!("zerg_warz_{}_{}.rhai", name, process::id()));
+        fs::write(&path, source).unwrap();
+        File::options().write(true).open(&path).unwrap().set_modified(UNIX_EPOCH + Duration::from_secs(second)).unwrap();
+        path.to_string_lossy().into_owned()
+    }
+
+    fn simulation() -> Simulation {
+        let mut setup = Setup::from_name("aivai").unwrap();
+        setup.set_factions(&["zerg", "terran"]);
+        Simulation::new(&setup, &GameData::bundled(), 1).unwrap()
+    }
+
+    #[test]
+    fn decides_on_spawns_and_commands() {
+        let path = write("decides", r#"fn decide(obs) { ["ling", #{type: "hold", position: 0.3}] }"#, 1);
+        let sim = simulation();
+        let mut script = Script::load(&path).unwrap();
+        assert_eq!(script.decide(&sim.players, 0, 0), vec![Command::Spawn(UnitKind::Ling), Command::Hold(0.3)]);
+        fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn edits_are_reloaded_and_broken_ones_ignored() {
+        let path = write("reload", r#"fn decide(obs) { ["ling"] }"#, 1);
+        let sim = simulation();
+        let mut script = Script::load(&path).unwrap();
+        assert_eq!(script.decide(&sim.players, 0, 0), vec![Command::Spawn(UnitKind::Ling)]);
+        write("reload", r#"fn decide(obs) { ["hydra"] }"#, 2);
+        assert_eq!(script.decide(&sim.players, 0, 1), vec![Command::Spawn(UnitKind::Hydra)]);
+        // A script that doesn't compile keeps running its last version
+        write("reload", r#"fn decide(obs) { ["bane"] "#, 3);
+        assert_eq!(script.decide(&sim.players, 0, 2), vec![Command::Spawn(UnitKind::Hydra)]);
+        fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn failing_scripts_issue_no_commands() {
+        let path = write("fails", r#"fn decide(obs) { throw "broken" }"#, 1);
+        let mut sim = simulation();
+        let mut script = Script::load(&path).unwrap();
+        assert_eq!(script.decide(&sim.players, 0, 0), vec![]);
+        write("fails", r#"fn decide(obs) { "ling" }"#, 2);
+        assert_eq!(script.decide(&sim.players, 0, 1), vec![]);
+        // The player doesn't forfeit either
+        sim.players[0].controltype = Controltype::Script(ScriptParams{path: path.clone(), interval: 0.0});
+        sim.tick().unwrap();
+        assert!(sim.players[0].is_alive());
+        fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn shipped_script_decides() {
+        let mut sim = simulation();
+        sim.players[0].minerals = 1000;
+        let mut script = Script::load(concat!(env!("CARGO_MANIFEST_DIR"), "/scripts/counter.rhai")).unwrap();
+        let commands = script.decide(&sim.players, 0, 0);
+        assert!(!script.failed);
+        assert!(!commands.is_empty());
+    }
+}
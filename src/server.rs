@@ -0,0 +1,175 @@
+use ggez::{GameResult, GameError};
+use serde_json;
+
+use std::io::{BufRead, BufReader};
+use std::net::{TcpListener, TcpStream};
+use std::time::Duration;
+
+use bot::{Bot, PROTOCOL_VERSION};
+use jsonlines::{self, HANDSHAKE_TIMEOUT};
+
+
+/// The first message of a bot connecting over TCP
+#[derive(Deserialize)]
+struct Claim{
+    /// Always `claim`
+    #[serde(rename = "type")]
+    kind: String,
+    /// Version of the protocol the bot speaks
+    version: u32,
+    /// Index of the player the bot wants to control, any unclaimed one if `None`
+    #[serde(default)]
+    player: Option<usize>,
+    /// Shown when the bot has claimed its player
+    #[serde(default)]
+    name: Option<String>,
+}
+
+/// The answer to a claim. Rejected bots are disconnected
+#[derive(Serialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+enum Answer<'a>{
+    Welcome{
+        version: u32,
+        player: usize,
+    },
+    Rejected{
+        reason: &'a str,
+    },
+}
+
+/// Listens on a local TCP port for bots claiming players, which then talk the same protocol as the bots
+/// started by the match over their stdin and stdout, see `bot::Bot`
+#[derive(Debug)]
+pub struct Server{
+    listener: TcpListener,
+}
+
+impl Server {
+    /// Listens on the passed port of localhost, any free port if 0
+    pub fn bind(port: u16) -> GameResult<Server> {
+        Ok(Server{
+            listener: TcpListener::bind(("127.0.0.1", port))?,
+        })
+    }
+
+    /// Returns the port the server listens on
+    pub fn port(&self) -> GameResult<u16> {
+        Ok(self.listener.local_addr()?.port())
+    }
+
+    /// Waits until bots have claimed each of the players at the passed indices, returning them by player.
+    /// Bots with an invalid claim are rejected and the server keeps waiting
+    pub fn accept(&self, players: &[usize]) -> GameResult<Vec<(usize, Bot)>> {
+        let mut bots: Vec<(usize, Bot)> = Vec::with_capacity(players.len());
+        while bots.len() < players.len() {
+            let (stream, _) = self.listener.accept()?;
+            let open: Vec<usize> = players.iter().cloned().filter(|&player| bots.iter().all(|&(i, _)| i != player)).collect();
+            match handshake(stream, &open) {
+                Ok((player, name, bot)) => {
+                    println!("{} claimed player {}", name, player + 1);
+                    bots.push((player, bot));
+                }
+                Err(e) => println!("Rejected a bot: {}", e),
+            }
+        }
+        Ok(bots)
+    }
+}
+
+/// Reads the claim of a freshly connected bot and answers it.
+/// Returns the claimed player and the name of the bot, ready to play
+fn handshake(stream: TcpStream, open: &[usize]) -> GameResult<(usize, String, Bot)> {
+    stream.set_read_timeout(Some(Duration::from_secs(HANDSHAKE_TIMEOUT)))?;
+    let mut input = BufReader::new(stream.try_clone()?);
+    let mut line = String::new();
+    input.read_line(&mut line)?;
+    let claimed = match serde_json::from_str::<Claim>(&line) {
+        Err(e) => Err(format!("invalid claim {:?}: {}", line.trim(), e)),
+        Ok(ref claim) if claim.kind != "claim" => Err(format!("expected a claim, got {}", claim.kind)),
+        Ok(ref claim) if claim.version != PROTOCOL_VERSION => {
+            Err(format!("protocol version {} is not supported, expected {}", claim.version, PROTOCOL_VERSION))
+        }
+        Ok(claim) => {
+            let name = claim.name.unwrap_or_else(|| String::from("A bot"));
+            match claim.player {
+                Some(player) if open.contains(&player) => Ok((player, name)),
+                Some(player) => Err(format!("player {} can't be claimed", player)),
+                None => open.first().map(|&player| (player, name)).ok_or_else(|| String::from("no player left to claim")),
+            }
+        }
+    };
+    let answer = match claimed {
+        Ok((player, _)) => Answer::Welcome{version: PROTOCOL_VERSION, player: player},
+        Err(ref reason) => Answer::Rejected{reason: reason},
+    };
+    jsonlines::write(&stream, &answer)?;
+    match claimed {
+        Ok((player, name)) => {
+            // From now on, answers time out as configured for the player
+            stream.set_read_timeout(None)?;
+            Ok((player, name, Bot::connected(stream, input)))
+        }
+        Err(reason) => Err(GameError::UnknownError(reason)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::Value;
+    use std::thread;
+
+    /// Connects to the server on the passed port and sends it each claim in turn,
+    /// returning the answers and keeping the connections of welcomed bots open
+    fn claim(port: u16, claims: Vec<String>) -> thread::JoinHandle<(Vec<Value>, Vec<TcpStream>)> {
+        thread::spawn(move || {
+            let mut answers = Vec::new();
+            let mut streams = Vec::new();
+            for claim in claims {
+                let stream = TcpStream::connect(("127.0.0.1", port)).unwrap();
+                let mut input = BufReader::new(stream.try_clone().unwrap());
+                jsonlines::write(&stream, &serde_json::from_str::<Value>(&claim).unwrap()).unwrap();
+                answers.push(jsonlines::read(&mut input).unwrap());
+                streams.push(stream);
+            }
+            (answers, streams)
+        })
+    }
+
+    fn claimed(version: u32, player: &str) -> String {
+        format!(r#"{{"type": "claim", "version": {}, "player": {}, "name": "test"}}"#, version, player)
+    }
+
+    #[test]
+    fn claims_are_welcomed_with_their_player() {
+        let server = Server::bind(0).unwrap();
+        let client = claim(server.port().unwrap(), vec![claimed(PROTOCOL_VERSION, "2"), claimed(PROTOCOL_VERSION, "null")]);
+        let bots = server.accept(&[1, 2]).unwrap();
+        let (answers, _) = client.join().unwrap();
+        assert_eq!(bots.iter().map(|&(player, _)| player).collect::<Vec<usize>>(), vec![2, 1]);
+        assert_eq!(answers[0], serde_json::from_str::<Value>(&format!(r#"{{"type": "welcome", "version": {}, "player": 2}}"#, PROTOCOL_VERSION)).unwrap());
+        // Without a player, the bot gets the first one still open
+        assert_eq!(answers[1]["type"], "welcome");
+        assert_eq!(answers[1]["player"], 1);
+    }
+
+    #[test]
+    fn invalid_claims_are_rejected_and_the_server_keeps_waiting() {
+        let server = Server::bind(0).unwrap();
+        let client = claim(server.port().unwrap(), vec![
+            claimed(PROTOCOL_VERSION + 1, "0"),
+            format!(r#"{{"type": "join", "version": {}}}"#, PROTOCOL_VERSION),
+            claimed(PROTOCOL_VERSION, "5"),
+            claimed(PROTOCOL_VERSION, "0"),
+            claimed(PROTOCOL_VERSION, "0"),
+            claimed(PROTOCOL_VERSION, "1"),
+        ]);
+        let bots = server.accept(&[0, 1]).unwrap();
+        let (answers, _) = client.join().unwrap();
+        let kinds: Vec<&str> = answers.iter().map(|answer| answer["type"].as_str().unwrap()).collect();
+        assert_eq!(kinds, vec!["rejected", "rejected", "rejected", "welcome", "rejected", "welcome"]);
+        assert!(answers[0]["reason"].as_str().unwrap().contains("protocol version"));
+        assert_eq!(bots.iter().map(|&(player, _)| player).collect::<Vec<usize>>(), vec![0, 1]);
+    }
+}
@@ -0,0 +1,111 @@
+use gamestate::Side;
+use player::Controltype;
+
+
+/// One end of a lane, where a player's base is located
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct LaneEnd{
+    pub lane: usize,
+    pub side: Side,
+}
+
+/// The place of a single player in a `Setup`
+#[derive(Debug, Clone)]
+pub struct Slot{
+    pub team: usize,
+    /// The lane ends the player's base borders on. Units can be sent down each of these lanes
+    pub ends: Vec<LaneEnd>,
+    pub ai: bool,
+    /// Name of the player's faction in the data file
+    pub faction: String,
+    /// Controls the player if it is an AI, the `SimpleAI` if `None`
+    pub controller: Option<Controltype>,
+}
+
+/// Which players take part in a match, and how the lanes of the map connect their bases.
+/// A lane end may only be shared by players of the same team
+#[derive(Debug, Clone)]
+pub struct Setup{
+    pub lanes: usize,
+    pub slots: Vec<Slot>,
+}
+
+impl Setup {
+    /// Returns the setup with the passed name, one of `1v1`, `1vai`, `aivai`, `2v2`, `ffa2`, `ffa3` and `ffa4`
+    pub fn from_name(name: &str) -> Option<Setup> {
+        match name {
+            "1v1" => Some(Setup::one_vs_one(false, false)),
+            "1vai" => Some(Setup::one_vs_one(false, true)),
+            "aivai" => Some(Setup::one_vs_one(true, true)),
+            "2v2" => Some(Setup::two_vs_two()),
+            "ffa2" => Some(Setup::free_for_all(2)),
+            "ffa3" => Some(Setup::free_for_all(3)),
+            "ffa4" => Some(Setup::free_for_all(4)),
+            _ => None,
+        }
+    }
+
+    /// Assigns the passed factions to the slots in order. Slots without a faction in the list keep theirs
+    pub fn set_factions(&mut self, factions: &[&str]){
+        for (slot, faction) in self.slots.iter_mut().zip(factions) {
+            slot.faction = faction.to_string();
+        }
+    }
+
+    /// Hands all AI slots to the passed controller
+    pub fn set_controller(&mut self, controller: &Controltype){
+        for slot in self.slots.iter_mut().filter(|slot| slot.ai) {
+            slot.controller = Some(controller.clone());
+        }
+    }
+
+    /// Two players on a single lane
+    pub fn one_vs_one(left_ai: bool, right_ai: bool) -> Setup {
+        Setup{
+            lanes: 1,
+            slots: vec!(
+                Slot{team: 0, ends: vec!(LaneEnd{lane: 0, side: Side::Left}), ai: left_ai, faction: zerg(), controller: None},
+                Slot{team: 1, ends: vec!(LaneEnd{lane: 0, side: Side::Right}), ai: right_ai, faction: zerg(), controller: None},
+            ),
+        }
+    }
+
+    /// Two teams of a human and an AI player each, sharing a single lane
+    pub fn two_vs_two() -> Setup {
+        let left = vec!(LaneEnd{lane: 0, side: Side::Left});
+        let right = vec!(LaneEnd{lane: 0, side: Side::Right});
+        Setup{
+            lanes: 1,
+            slots: vec!(
+                Slot{team: 0, ends: left.clone(), ai: false, faction: zerg(), controller: None},
+                Slot{team: 0, ends: left, ai: true, faction: zerg(), controller: None},
+                Slot{team: 1, ends: right.clone(), ai: false, faction: zerg(), controller: None},
+                Slot{team: 1, ends: right, ai: true, faction: zerg(), controller: None},
+            ),
+        }
+    }
+
+    /// `players` players on their own teams, with the lanes forming a ring so that every player
+    /// borders on two neighbours. The first two players are human, all others AI
+    pub fn free_for_all(players: usize) -> Setup {
+        let slots = (0..players).map(|i| Slot{
+            team: i,
+            ends: vec!(
+                LaneEnd{lane: i, side: Side::Left},
+                LaneEnd{lane: (i + players - 1) % players, side: Side::Right},
+            ),
+            ai: i >= 2,
+            faction: zerg(),
+            controller: None,
+        }).collect();
+        Setup{
+            lanes: players,
+            slots: slots,
+        }
+    }
+}
+
+/// The faction every slot plays unless told otherwise
+fn zerg() -> String {
+    String::from("zerg")
+}
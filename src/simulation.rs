@@ -0,0 +1,420 @@
+use ggez::{GameResult, GameError};
+use std::mem;
+use std::sync::{Arc, Mutex};
+
+use player::{Player, Command, Controltype};
+use gameobject::{GameChar, Base};
+use data::GameData;
+use objective::Objective;
+use setup::Setup;
+use dice::Dice;
+use results::MatchResult;
+use save::SaveGame;
+use scenario::Scenario;
+use constants::TICKS_PER_SECOND;
+use mcts;
+use bot::{Bot, BotParams};
+use script::{Script, ScriptParams};
+use behavior::Trace;
+
+
+/// A match without any graphics or input handling, advancing in fixed ticks.
+/// It is driven by `GameState` when played in a window, and directly by the headless tools
+#[derive(Debug)]
+pub struct Simulation{
+    pub lanes: usize,
+    pub players: Vec<Player>,
+    /// Indices into `players` of the human players, in the order of their key bindings
+    pub humans: Vec<usize>,
+    pub objectives: Vec<Objective>,
+    /// Set once at most one team is left standing
+    pub over: bool,
+    /// The team left standing, `None` if the last teams have lost their bases at the same time
+    pub winner: Option<usize>,
+    /// Drives all random elements of the simulation
+    pub dice: Dice,
+    /// Number of simulation ticks since the start of the match
+    pub tick: u64,
+    /// The scenario being played, if any. Scenarios end as soon as their win condition is decided
+    pub scenario: Option<Scenario>,
+    /// Whether the win condition of the scenario has been fulfilled, once it is decided
+    pub passed: Option<bool>,
+    /// The running external bots, by index of their player. Programs are started at their first decision
+    bots: Vec<(usize, Arc<Mutex<Bot>>)>,
+    /// The loaded scripts, by index of their player. Scripts are loaded at their first decision
+    scripts: Vec<(usize, Arc<Mutex<Script>>)>,
+    /// Whether this is the real match rather than a clone played forward by a search.
+    /// Only the real match starts bots and scripts and tells bots the outcome
+    owns_bots: bool,
+    /// The nodes each behavior tree evaluated on its last decision, by index of its player.
+    /// Not part of saves, spectators get them with each snapshot
+    pub traces: Vec<(usize, Vec<Trace>)>,
+}
+
+/// Clones play without the bots and scripts of the real match, so they can't disturb them.
+/// Their external and scripted players issue no commands
+impl Clone for Simulation {
+    fn clone(&self) -> Simulation {
+        Simulation{
+            lanes: self.lanes,
+            players: self.players.clone(),
+            humans: self.humans.clone(),
+            objectives: self.objectives.clone(),
+            over: self.over,
+            winner: self.winner,
+            dice: self.dice.clone(),
+            tick: self.tick,
+            scenario: self.scenario.clone(),
+            passed: self.passed,
+            bots: Vec::new(),
+            scripts: Vec::new(),
+            owns_bots: false,
+            traces: Vec::new(),
+        }
+    }
+}
+
+impl Simulation {
+    /// Returns a new match with the passed setup, whose randomness derives from `seed`.
+    /// Every lane gets its own copy of the neutral objectives
+    pub fn new(setup: &Setup, data: &GameData, seed: u64) -> GameResult<Simulation>{
+        let mut players = Vec::with_capacity(setup.slots.len());
+        for slot in &setup.slots {
+            players.push(Player::new(slot, data)?);
+        }
+        let mut objectives = Vec::new();
+        for lane in 0..setup.lanes {
+            objectives.extend(data.objectives.iter().map(|def| Objective::new(def, lane)));
+        }
+        Ok(Simulation::from_save(SaveGame{
+            lanes: setup.lanes,
+            players: players,
+            humans: setup.slots.iter().enumerate().filter(|&(_, slot)| !slot.ai).map(|(i, _)| i).collect(),
+            objectives: objectives,
+            over: false,
+            winner: None,
+            dice: Dice::new(seed, data.randomness),
+            tick: 0,
+            scenario: None,
+        }))
+    }
+
+    /// Returns a match that resumes the passed saved match
+    pub fn from_save(save: SaveGame) -> Simulation{
+        Simulation{
+            lanes: save.lanes,
+            players: save.players,
+            humans: save.humans,
+            objectives: save.objectives,
+            over: save.over,
+            winner: save.winner,
+            dice: save.dice,
+            tick: save.tick,
+            scenario: save.scenario,
+            passed: None,
+            bots: Vec::new(),
+            scripts: Vec::new(),
+            owns_bots: true,
+            traces: Vec::new(),
+        }
+    }
+
+    /// Returns the complete state of the match, ready to be written to a file
+    pub fn save(&self) -> SaveGame{
+        SaveGame{
+            lanes: self.lanes,
+            players: self.players.clone(),
+            humans: self.humans.clone(),
+            objectives: self.objectives.clone(),
+            over: self.over,
+            winner: self.winner,
+            dice: self.dice.clone(),
+            tick: self.tick,
+            scenario: self.scenario.clone(),
+        }
+    }
+
+    /// Returns the result of the match so far
+    pub fn result(&self) -> MatchResult{
+        MatchResult{
+            seed: self.dice.seed(),
+            ticks: self.tick,
+            winner: self.winner,
+            factions: self.players.iter().map(|player| player.faction.clone()).collect(),
+            scenario: self.scenario.as_ref().map(|scenario| scenario.name.clone()),
+        }
+    }
+
+    /// Executes a command of the human player at the passed index into `humans`.
+    /// Commands the player can't afford are simply ignored, as are those of players that have lost
+    pub fn command(&mut self, human: usize, command: Command){
+        if let Some(&i) = self.humans.get(human){
+            if !self.over && self.players[i].is_alive(){
+                let _ = self.players[i].execute(command, &mut self.dice);
+            }
+        }
+    }
+
+    /// Advances the simulation by a single tick: units fight and move, objectives change hands,
+    /// AI players decide and everyone gains income
+    pub fn tick(&mut self) -> GameResult<()> {
+        // The match is over once a single team is left
+        if self.over{
+            return Ok(());
+        }
+        self.tick += 1;
+
+        // Deal damage
+        // The following implementation seems to favour the players whose units attack first
+        // But as units with 0 hp are only sorted out at the end of the tick, this is not an issue
+        // (effectively allowing units whose hp were reduced to 0 or bellows to attack 1 more time)
+        for i in 0..self.players.len(){
+            // Take the units out of the player, so they can attack the units of all other teams
+            let mut units = mem::replace(&mut self.players[i].units, Vec::new());
+            {
+                let team = self.players[i].team;
+                let mut enemies: Vec<&mut GameChar> = Vec::new();
+                let mut bases: Vec<&mut Base> = Vec::new();
+                for player in self.players.iter_mut().filter(|player| player.team != team){
+                    let Player{ref mut units, ref mut base, ..} = *player;
+                    enemies.extend(units.iter_mut());
+                    bases.push(base);
+                }
+                for unit in &mut units{
+                    unit.attack_move(&mut enemies, &mut bases, &mut self.objectives, &mut self.dice);
+                }
+            }
+            self.players[i].units = units;
+        }
+
+        // Neutral objectives change hands and fight back,
+        // killed camps reward the units of the players at the killing lane end
+        let delta = 1.0/TICKS_PER_SECOND as f32;
+        for objective in &mut self.objectives {
+            if let Some((end, status)) = objective.update(&mut self.players, delta) {
+                for player in self.players.iter_mut().filter(|player| player.base.ends.contains(&end)) {
+                    for unit in &mut player.units {
+                        unit.apply_status(&status);
+                    }
+                }
+            }
+        }
+
+        // Players that have lost their base are out, together with their army
+        for player in &mut self.players{
+            if !player.is_alive(){
+                player.units.clear();
+            }
+        }
+        let mut teams: Vec<usize> = self.players.iter().filter(|player| player.is_alive()).map(|player| player.team).collect();
+        teams.sort();
+        teams.dedup();
+        if teams.len() <= 1 && self.scenario.is_none(){
+            self.over = true;
+            self.winner = teams.first().cloned();
+        }
+
+        // Searching AIs look at a clone of the whole match every few ticks and keep spawning the unit they decided on
+        // until the next search. Their decisions to give army orders are executed right away
+        let mut searched: Vec<Vec<Command>> = vec!(Vec::new(); self.players.len());
+        for i in 0..self.players.len(){
+            let decision = match self.players[i].controltype {
+                Controltype::Mcts{ref params, ..} if self.players[i].is_alive() && self.tick % params.interval_ticks() == 0 => {
+                    Some(mcts::decide(self, i, params))
+                }
+                _ => None,
+            };
+            if let Some(decision) = decision{
+                if let Controltype::Mcts{ref mut plan, ..} = self.players[i].controltype{
+                    *plan = match decision {
+                        Some(Command::Spawn(kind)) => Some(kind),
+                        _ => None,
+                    };
+                }
+                searched[i].extend(decision.into_iter().filter(|command| match *command {
+                    Command::Spawn(_) => false,
+                    _ => true,
+                }));
+            }
+        }
+
+        // External bots are sent the match every few ticks and answer with their commands.
+        // Bots that fail to answer in time or break the protocol forfeit
+        for i in 0..self.players.len(){
+            let params = match self.players[i].controltype {
+                Controltype::External(ref params) if self.players[i].is_alive() && self.tick % params.interval_ticks() == 0 => params.clone(),
+                _ => continue,
+            };
+            match self.bot_decision(i, &params) {
+                Ok(commands) => searched[i].extend(commands),
+                Err(e) => {
+                    println!("Player {} forfeits: {}", i + 1, e);
+                    self.players[i].base.hp = 0.0;
+                }
+            }
+        }
+
+        // Scripts are asked for their commands every few ticks. Players whose script can't be loaded forfeit
+        for i in 0..self.players.len(){
+            let params = match self.players[i].controltype {
+                Controltype::Script(ref params) if self.players[i].is_alive() && self.tick % params.interval_ticks() == 0 => params.clone(),
+                _ => continue,
+            };
+            match self.script_decision(i, &params) {
+                Ok(commands) => searched[i].extend(commands),
+                Err(e) => {
+                    println!("Player {} forfeits: {}", i + 1, e);
+                    self.players[i].base.hp = 0.0;
+                }
+            }
+        }
+
+        // Let AI players decide what to build and research, together with their allies
+        let mut commands: Vec<Vec<Command>> = Vec::with_capacity(self.players.len());
+        let mut traces = Vec::new();
+        {
+            let players = &self.players;
+            for (i, (player, searched)) in players.iter().zip(searched).enumerate(){
+                // AIs with a slower reaction only decide every few ticks
+                let reacts = match player.controltype {
+                    Controltype::SimpleAI(ref params) => self.tick % params.reaction_ticks() == 0,
+                    _ => true,
+                };
+                if !player.is_alive() || !reacts{
+                    commands.push(searched);
+                    continue;
+                }
+                let others = || players.iter().enumerate().filter(|&(j, other)| j != i && other.is_alive()).map(|(_, other)| other);
+                let allies: Vec<&Player> = others().filter(|other| other.team == player.team).collect();
+                let opponents: Vec<&Player> = others().filter(|other| other.team != player.team).collect();
+                let mut decided = searched;
+                match player.controltype {
+                    Controltype::BehaviorTree(ref tree) => {
+                        let (decision, trace) = tree.decide_traced(player, &opponents);
+                        decided.extend(decision);
+                        traces.push((i, trace));
+                    }
+                    _ => decided.extend(player.build_decision(&allies, &opponents, &self.objectives, &mut self.dice)),
+                }
+                commands.push(decided);
+            }
+        }
+        self.traces = traces;
+        for (player, commands) in self.players.iter_mut().zip(commands){
+            for command in commands{
+                let _ = player.execute(command, &mut self.dice);
+            }
+        }
+
+        //this could potentially be adapted to be multithreaded
+        for player in self.players.iter_mut().filter(|player| player.is_alive()){
+            //Gain resources, including those of held mineral fields
+            player.minerals += 1;
+            for objective in &self.objectives{
+                if let Some((end, income)) = objective.income(){
+                    if player.base.ends.contains(&end){
+                        player.minerals += income;
+                    }
+                }
+            }
+
+            //progress morphs and abilities
+            player.update_units(delta)?;
+
+            //remove dead units
+            let mut living_units = Vec::with_capacity(player.units.capacity());
+            for unit in &player.units{
+                if unit.stats.hp > 0.0{
+                    living_units.push(unit.clone());
+                }
+            }
+            player.units = living_units;
+        }
+
+        // Scenarios end as soon as their win condition is decided
+        let decided = match self.scenario {
+            Some(ref scenario) => scenario.win.check(&self.players, self.tick).map(|passed| (scenario.win.player(), passed)),
+            None => None,
+        };
+        if let Some((player, passed)) = decided{
+            let team = self.players[player].team;
+            self.over = true;
+            self.winner = if passed {
+                Some(team)
+            } else {
+                self.players.iter().map(|player| player.team).find(|&other| other != team)
+            };
+            self.passed = Some(passed);
+        }
+
+        // Bots are told the outcome and terminated
+        if self.over && self.owns_bots{
+            for (_, bot) in self.bots.drain(..){
+                if let Ok(mut bot) = bot.lock(){
+                    bot.finish(self.winner);
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Returns the commands the external bot of the player at the passed index answers with, starting it if necessary
+    fn bot_decision(&mut self, player: usize, params: &BotParams) -> GameResult<Vec<Command>> {
+        if !self.owns_bots {
+            return Ok(Vec::new());
+        }
+        let bot = match self.bots.iter().find(|&&(i, _)| i == player) {
+            Some(&(_, ref bot)) => bot.clone(),
+            None => {
+                let bot = Arc::new(Mutex::new(Bot::start(params)?));
+                self.bots.push((player, bot.clone()));
+                bot
+            }
+        };
+        let mut bot = bot.lock().map_err(|_| GameError::UnknownError(String::from("bot is unavailable")))?;
+        bot.decide(&self.players, player, self.lanes, self.tick, params.timeout)
+    }
+
+    /// Returns the commands the script of the player at the passed index decides on, loading it if necessary
+    fn script_decision(&mut self, player: usize, params: &ScriptParams) -> GameResult<Vec<Command>> {
+        if !self.owns_bots {
+            return Ok(Vec::new());
+        }
+        let script = match self.scripts.iter().find(|&&(i, _)| i == player) {
+            Some(&(_, ref script)) => script.clone(),
+            None => {
+                let script = Arc::new(Mutex::new(Script::load(&params.path)?));
+                self.scripts.push((player, script.clone()));
+                script
+            }
+        };
+        let mut script = script.lock().map_err(|_| GameError::UnknownError(String::from("script is unavailable")))?;
+        Ok(script.decide(&self.players, player, self.tick))
+    }
+
+    /// Hands the player at the passed index to a bot that has already been started or has connected
+    pub fn attach(&mut self, player: usize, bot: Bot){
+        self.bots.retain(|&(i, _)| i != player);
+        self.bots.push((player, Arc::new(Mutex::new(bot))));
+    }
+
+    /// Returns the nodes the behavior tree of the passed player evaluated on its last decision, empty for other players
+    pub fn trace(&self, player: usize) -> &[Trace]{
+        self.traces.iter().find(|&&(i, _)| i == player).map(|(_, trace)| trace.as_slice()).unwrap_or(&[])
+    }
+
+    /// Takes over the bots and scripts of the passed match, e.g. when a saved match is loaded in its place
+    pub fn adopt(&mut self, other: &mut Simulation){
+        self.bots = mem::replace(&mut other.bots, Vec::new());
+        self.scripts = mem::replace(&mut other.scripts, Vec::new());
+    }
+
+    /// Runs the match until it is over or has lasted `seconds`, whichever comes first
+    pub fn run(&mut self, seconds: f32) -> GameResult<()> {
+        let ticks = (seconds*TICKS_PER_SECOND as f32) as u64;
+        while !self.over && self.tick < ticks {
+            self.tick()?;
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,273 @@
+use ggez::{GameResult, GameError};
+use serde_json;
+
+use std::io::{BufReader, Write};
+use std::net::{SocketAddr, TcpListener, TcpStream};
+use std::sync::mpsc::{self, Receiver, Sender, TryRecvError};
+use std::thread;
+use std::time::Duration;
+
+use save::SaveGame;
+use simulation::Simulation;
+use behavior::Trace;
+use jsonlines::{self, HANDSHAKE_TIMEOUT};
+
+
+/// Version of the protocol, spectators have to speak the same as the broadcasting match
+pub const PROTOCOL_VERSION: u32 = 1;
+
+/// Milliseconds a spectator may take to receive a snapshot before it is dropped, so it can't hold up the match
+const WRITE_TIMEOUT: u64 = 200;
+
+/// A message between a broadcasting match and a spectator, written as a single line of json
+#[derive(Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+enum Message{
+    /// Sent by a spectator when it connects
+    Spectate{
+        version: u32,
+    },
+    /// The answer to a valid `spectate`, followed by the first snapshot
+    Welcome{
+        version: u32,
+    },
+    /// The answer to an invalid `spectate`, after which the spectator is disconnected
+    Rejected{
+        reason: String,
+    },
+    /// The complete match, sent every few ticks, with what its behavior trees last evaluated
+    Snapshot{
+        save: Box<SaveGame>,
+        #[serde(default)]
+        traces: Vec<(usize, Vec<Trace>)>,
+    },
+}
+
+/// Listens on a TCP port for spectators and sends them snapshots of the match.
+/// Spectators only watch, whatever the match is: local, networked or between AIs
+#[derive(Debug)]
+pub struct Broadcast{
+    /// The address spectators connect to
+    address: SocketAddr,
+    /// Spectators that have been welcomed, accepted on a separate thread so a slow handshake doesn't hold up the match
+    welcomed: Receiver<(TcpStream, String)>,
+    spectators: Vec<TcpStream>,
+    /// Ticks between two snapshots
+    interval: u64,
+    /// The tick the last snapshot was sent at
+    sent: Option<u64>,
+}
+
+impl Broadcast {
+    /// Listens on the passed address, e.g. `127.0.0.1:7778` or any free port if 0, sending a snapshot every `interval` ticks
+    pub fn bind(address: &str, interval: u64) -> GameResult<Broadcast> {
+        let listener = TcpListener::bind(address)?;
+        let address = listener.local_addr()?;
+        println!("Broadcasting the match to spectators on {}", address);
+        let (sender, welcomed) = mpsc::channel();
+        thread::spawn(move || accept(listener, sender));
+        Ok(Broadcast{
+            address: address,
+            welcomed: welcomed,
+            spectators: Vec::new(),
+            interval: interval.max(1),
+            sent: None,
+        })
+    }
+
+    /// Returns the address spectators connect to
+    pub fn address(&self) -> SocketAddr {
+        self.address
+    }
+
+    /// Lets in the spectators welcomed since the last call and sends the match to all of them if a snapshot is due.
+    /// Spectators that have disconnected or can't keep up are dropped
+    pub fn update(&mut self, sim: &Simulation) -> GameResult<()> {
+        let mut joined = false;
+        while let Ok((stream, address)) = self.welcomed.try_recv() {
+            println!("{} is spectating", address);
+            self.spectators.push(stream);
+            joined = true;
+        }
+        // A snapshot is also due once the match is over, so spectators see how it ended
+        let due = self.sent != Some(sim.tick) && (sim.tick % self.interval == 0 || sim.over);
+        if self.spectators.is_empty() || !(due || joined) {
+            return Ok(());
+        }
+        let line = serde_json::to_string(&Message::Snapshot{save: Box::new(sim.save()), traces: sim.traces.clone()})
+            .map_err(|e| GameError::UnknownError(format!("could not write snapshot: {}", e)))?;
+        self.spectators.retain(|mut stream| {
+            let written = writeln!(stream, "{}", line).and_then(|_| stream.flush()).is_ok();
+            if !written {
+                println!("A spectator has left");
+            }
+            written
+        });
+        self.sent = Some(sim.tick);
+        Ok(())
+    }
+}
+
+/// Welcomes every spectator that connects, each on its own thread so a silent one doesn't keep the others waiting,
+/// and passes them on to the broadcast
+fn accept(listener: TcpListener, sender: Sender<(TcpStream, String)>) {
+    for stream in listener.incoming() {
+        let stream = match stream {
+            Ok(stream) => stream,
+            Err(_) => continue,
+        };
+        let sender = sender.clone();
+        thread::spawn(move || {
+            let address = stream.peer_addr().map(|address| address.to_string()).unwrap_or_default();
+            match welcome(stream) {
+                Ok(stream) => {
+                    let _ = sender.send((stream, address));
+                }
+                Err(e) => println!("Rejected a spectator: {}", e),
+            }
+        });
+    }
+}
+
+/// Reads the request of a freshly connected spectator and answers it
+fn welcome(stream: TcpStream) -> GameResult<TcpStream> {
+    stream.set_read_timeout(Some(Duration::from_secs(HANDSHAKE_TIMEOUT)))?;
+    stream.set_write_timeout(Some(Duration::from_millis(WRITE_TIMEOUT)))?;
+    let mut input = BufReader::new(stream.try_clone()?);
+    let requested = match jsonlines::read(&mut input)? {
+        Message::Spectate{version} if version == PROTOCOL_VERSION => Ok(()),
+        Message::Spectate{version} => Err(format!("protocol version {} is not supported, expected {}", version, PROTOCOL_VERSION)),
+        _ => Err(String::from("expected a spectate request")),
+    };
+    match requested {
+        Ok(()) => {
+            jsonlines::write(&stream, &Message::Welcome{version: PROTOCOL_VERSION})?;
+            Ok(stream)
+        }
+        Err(reason) => {
+            let _ = jsonlines::write(&stream, &Message::Rejected{reason: reason.clone()});
+            Err(GameError::UnknownError(reason))
+        }
+    }
+}
+
+/// Watches a match broadcast over TCP, see `Broadcast`
+#[derive(Debug)]
+pub struct Spectator{
+    /// Lines the match has written, read on a separate thread so the game doesn't block
+    lines: Receiver<String>,
+    /// Whether the match has stopped broadcasting
+    ended: bool,
+}
+
+impl Spectator {
+    /// Connects to the match broadcast at the passed address, e.g. `127.0.0.1:7778`,
+    /// and returns the spectator with the first snapshot of the match
+    pub fn connect(address: &str) -> GameResult<(Spectator, SaveGame)> {
+        let stream = TcpStream::connect(address)?;
+        stream.set_read_timeout(Some(Duration::from_secs(HANDSHAKE_TIMEOUT)))?;
+        let mut input = BufReader::new(stream.try_clone()?);
+        jsonlines::write(&stream, &Message::Spectate{version: PROTOCOL_VERSION})?;
+        match jsonlines::read(&mut input)? {
+            Message::Welcome{..} => {}
+            Message::Rejected{reason} => return Err(GameError::UnknownError(format!("the match rejected the spectator: {}", reason))),
+            _ => return Err(GameError::UnknownError(String::from("expected a welcome"))),
+        }
+        let save = match jsonlines::read(&mut input)? {
+            Message::Snapshot{save, ..} => *save,
+            _ => return Err(GameError::UnknownError(String::from("expected a snapshot"))),
+        };
+        stream.set_read_timeout(None)?;
+        Ok((Spectator{lines: jsonlines::lines(input), ended: false}, save))
+    }
+
+    /// Returns the match as of the newest snapshot received since the last call, if any
+    pub fn latest(&mut self) -> GameResult<Option<Simulation>> {
+        let mut latest = None;
+        loop {
+            match self.lines.try_recv() {
+                Ok(line) => match jsonlines::parse(&line)? {
+                    Message::Snapshot{save, traces} => latest = Some((save, traces)),
+                    _ => return Err(GameError::UnknownError(String::from("expected a snapshot"))),
+                },
+                Err(TryRecvError::Empty) => break,
+                Err(TryRecvError::Disconnected) => {
+                    if !self.ended {
+                        println!("The match has stopped broadcasting");
+                        self.ended = true;
+                    }
+                    break;
+                }
+            }
+        }
+        Ok(latest.map(|(save, traces)| {
+            let mut sim = Simulation::from_save(*save);
+            sim.traces = traces;
+            sim
+        }))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use behavior::BehaviorTree;
+    use data::GameData;
+    use player::Controltype;
+    use setup::Setup;
+    use std::time::Instant;
+
+    #[test]
+    fn silent_spectator_does_not_hold_up_the_match() {
+        let data = GameData::bundled();
+        let mut sim = Simulation::new(&Setup::from_name("aivai").unwrap(), &data, 5).unwrap();
+        let mut broadcast = Broadcast::bind("127.0.0.1:0", 3).unwrap();
+        let address = broadcast.address().to_string();
+        let _silent = TcpStream::connect(&address).unwrap();
+        let spectator = thread::spawn(move || Spectator::connect(&address).unwrap());
+        for _ in 0..500 {
+            sim.tick().unwrap();
+            let started = Instant::now();
+            broadcast.update(&sim).unwrap();
+            assert!(started.elapsed() < Duration::from_secs(1), "the match waited for a spectator");
+            if !broadcast.spectators.is_empty() {
+                break;
+            }
+            thread::sleep(Duration::from_millis(10));
+        }
+        let (_, save) = spectator.join().unwrap();
+        assert!(save.tick <= sim.tick);
+    }
+
+    #[test]
+    fn snapshots_carry_the_behavior_tree_traces() {
+        let data = GameData::bundled();
+        let mut sim = Simulation::new(&Setup::from_name("aivai").unwrap(), &data, 5).unwrap();
+        let tree: BehaviorTree = serde_json::from_str(r#"{"root": {"type": "action", "action": "retreat"}}"#).unwrap();
+        sim.players[0].controltype = Controltype::BehaviorTree(tree);
+        let mut broadcast = Broadcast::bind("127.0.0.1:0", 1).unwrap();
+        let address = broadcast.address().to_string();
+        let connecting = thread::spawn(move || Spectator::connect(&address).unwrap());
+        for _ in 0..500 {
+            sim.tick().unwrap();
+            broadcast.update(&sim).unwrap();
+            if !broadcast.spectators.is_empty() {
+                break;
+            }
+            thread::sleep(Duration::from_millis(10));
+        }
+        let (mut spectator, _) = connecting.join().unwrap();
+        sim.tick().unwrap();
+        broadcast.update(&sim).unwrap();
+        let mut watched = None;
+        for _ in 0..500 {
+            watched = spectator.latest().unwrap();
+            if watched.is_some() {
+                break;
+            }
+            thread::sleep(Duration::from_millis(10));
+        }
+        assert_eq!(watched.unwrap().trace(0), sim.trace(0));
+        assert!(!sim.trace(0).is_empty());
+    }
+}
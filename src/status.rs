@@ -0,0 +1,137 @@
+/// A timed status effect that attacks, abilities or objectives inflict on a `GameChar`, as defined in the data file
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct StatusEffect{
+    pub name: String,
+    pub kind: StatusKind,
+    /// Time in seconds the effect lasts after it has last been applied
+    pub duration: f32,
+    #[serde(default)]
+    pub stacking: Stacking,
+    /// Maximum number of stacks, only relevant for `Stacking::Stack`
+    #[serde(default = "one")]
+    pub max_stacks: u32,
+}
+
+fn one() -> u32 {
+    1
+}
+
+/// What a `StatusEffect` does to the affected unit, per stack
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "lowercase")]
+pub enum StatusKind{
+    /// Multiplies the unit's speed by `factor`
+    Slow{
+        factor: f32,
+    },
+    /// The unit can neither move nor attack
+    Stun,
+    /// Deals `damage` per second, ignoring armor, scaled by the damage scale relative to its default
+    Acid{
+        damage: f32,
+    },
+    /// Reduces the unit's armor by `armor`, possibly below zero
+    Shred{
+        armor: f32,
+    },
+    /// Multiplies the unit's damage by `damage`
+    Frenzy{
+        damage: f32,
+    },
+}
+
+/// How a `StatusEffect` behaves when it is applied to a unit that is already affected by it
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Stacking{
+    /// The duration starts over
+    Refresh,
+    /// Another stack is added, up to `max_stacks`, and the duration starts over
+    Stack,
+    /// Nothing happens until the effect has run out
+    Ignore,
+}
+
+impl Default for Stacking {
+    fn default() -> Stacking {
+        Stacking::Refresh
+    }
+}
+
+
+/// A `StatusEffect` currently affecting a `GameChar`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Status{
+    pub effect: StatusEffect,
+    pub stacks: u32,
+    /// Time in seconds until the effect runs out
+    pub remaining: f32,
+}
+
+impl Status {
+    /// Returns a new `Status` with a single stack of the passed effect
+    pub fn new(effect: &StatusEffect) -> Status {
+        Status{
+            effect: effect.clone(),
+            stacks: 1,
+            remaining: effect.duration,
+        }
+    }
+
+    /// Applies the effect again, following its stacking rule
+    pub fn reapply(&mut self){
+        match self.effect.stacking {
+            Stacking::Refresh => self.remaining = self.effect.duration,
+            Stacking::Stack => {
+                self.stacks = (self.stacks + 1).min(self.effect.max_stacks);
+                self.remaining = self.effect.duration;
+            }
+            Stacking::Ignore => (),
+        }
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn acid(stacking: Stacking) -> StatusEffect {
+        StatusEffect{
+            name: String::from("Acid"),
+            kind: StatusKind::Acid{damage: 2.0},
+            duration: 3.0,
+            stacking: stacking,
+            max_stacks: 2,
+        }
+    }
+
+    #[test]
+    fn refresh_restarts_the_duration_without_stacking() {
+        let mut status = Status::new(&acid(Stacking::Refresh));
+        status.remaining = 1.0;
+        status.reapply();
+        assert_eq!(status.remaining, 3.0);
+        assert_eq!(status.stacks, 1);
+    }
+
+    #[test]
+    fn stack_adds_stacks_up_to_the_maximum() {
+        let mut status = Status::new(&acid(Stacking::Stack));
+        status.remaining = 1.0;
+        status.reapply();
+        assert_eq!(status.stacks, 2);
+        assert_eq!(status.remaining, 3.0);
+        status.reapply();
+        assert_eq!(status.stacks, 2);
+    }
+
+    #[test]
+    fn ignore_leaves_the_status_as_it_is() {
+        let mut status = Status::new(&acid(Stacking::Ignore));
+        status.remaining = 1.0;
+        status.reapply();
+        assert_eq!(status.remaining, 1.0);
+        assert_eq!(status.stacks, 1);
+    }
+}
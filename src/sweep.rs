@@ -0,0 +1,290 @@
+use ggez::{GameResult, GameError};
+
+use std::fs::File;
+use std::io::Write;
+use std::thread;
+
+use gameobject::UnitKind;
+use data::GameData;
+use setup::Setup;
+use simulation::Simulation;
+use constants::{SimConfig, TICKS_PER_SECOND, GAME_TIMEOUT};
+
+
+/// The values of each scale to sweep over. Every combination of them is played
+#[derive(Debug, Clone)]
+pub struct Grid{
+    pub movement_speed: Vec<f32>,
+    pub damage_scale: Vec<f32>,
+    pub map_scale: Vec<f32>,
+}
+
+impl Grid {
+    /// Returns a grid of half, the same and double of each scale of the passed config
+    pub fn around(config: SimConfig) -> Grid {
+        let factors = [0.5, 1.0, 2.0];
+        Grid{
+            movement_speed: factors.iter().map(|factor| config.movement_speed*factor).collect(),
+            damage_scale: factors.iter().map(|factor| config.damage_scale*factor).collect(),
+            map_scale: factors.iter().map(|factor| config.map_scale*factor).collect(),
+        }
+    }
+
+    /// Returns every combination of the values of the grid
+    pub fn configs(&self) -> Vec<SimConfig> {
+        let mut configs = Vec::new();
+        for &movement_speed in &self.movement_speed {
+            for &damage_scale in &self.damage_scale {
+                for &map_scale in &self.map_scale {
+                    configs.push(SimConfig{
+                        movement_speed: movement_speed,
+                        damage_scale: damage_scale,
+                        map_scale: map_scale,
+                    });
+                }
+            }
+        }
+        configs
+    }
+}
+
+/// The outcome of all games played with a single config
+#[derive(Debug, Clone)]
+pub struct Batch{
+    pub config: SimConfig,
+    pub games: u32,
+    pub draws: u32,
+    /// Total seconds the decided games lasted
+    seconds: f32,
+    /// Units of each kind built by the winning teams, and by everyone, over all decided games
+    kinds: Vec<(UnitKind, u32, u32)>,
+}
+
+impl Batch {
+    /// Plays `games` games of the passed setup with the passed config on the current thread.
+    /// Game `n` rolls its dice from `seed + n`
+    fn play(config: SimConfig, data: &GameData, setup: &Setup, games: u32, seed: u64) -> GameResult<Batch> {
+        config.apply();
+        let mut batch = Batch{
+            config: config,
+            games: 0,
+            draws: 0,
+            seconds: 0.0,
+            kinds: Vec::new(),
+        };
+        for n in 0..games {
+            let mut sim = Simulation::new(setup, data, seed.wrapping_add(n as u64))?;
+            sim.run(GAME_TIMEOUT)?;
+            batch.games += 1;
+            let winner = match sim.winner {
+                Some(team) if sim.over => team,
+                _ => {
+                    batch.draws += 1;
+                    continue;
+                }
+            };
+            batch.seconds += sim.tick as f32/TICKS_PER_SECOND as f32;
+            for player in &sim.players {
+                for &(kind, count) in &player.built {
+                    let won = if player.team == winner {count} else {0};
+                    match batch.kinds.iter_mut().find(|&&mut (k, _, _)| k == kind) {
+                        Some(&mut (_, ref mut by_winners, ref mut total)) => {
+                            *by_winners += won;
+                            *total += count;
+                        }
+                        None => batch.kinds.push((kind, won, count)),
+                    }
+                }
+            }
+        }
+        Ok(batch)
+    }
+
+    /// Share of the units of the passed kind that were built by the winning team,
+    /// `None` if no decided game saw the kind
+    pub fn win_rate(&self, kind: UnitKind) -> Option<f32> {
+        self.kinds.iter()
+            .find(|&&(k, _, _)| k == kind)
+            .map(|&(_, by_winners, total)| by_winners as f32/total.max(1) as f32)
+    }
+
+    /// Average seconds the decided games lasted, `None` if every game was a draw
+    pub fn game_length(&self) -> Option<f32> {
+        let decided = self.games - self.draws;
+        if decided == 0 {
+            None
+        } else {
+            Some(self.seconds/decided as f32)
+        }
+    }
+}
+
+/// AI-vs-AI batches for every config of a `Grid`, showing how the win rate of each unit kind
+/// changes with the scales
+#[derive(Debug, Clone)]
+pub struct Sweep{
+    pub batches: Vec<Batch>,
+    /// All unit kinds built in any batch, in order of first appearance
+    pub kinds: Vec<UnitKind>,
+}
+
+impl Sweep {
+    /// Plays `games` games of the passed setup for every config of the grid,
+    /// spreading the configs over `threads` threads
+    pub fn run(data: &GameData, setup: &Setup, grid: &Grid, games: u32, seed: u64, threads: usize) -> GameResult<Sweep> {
+        let configs = grid.configs();
+        let threads = threads.max(1);
+        let handles: Vec<_> = (0..threads).map(|t| {
+            let configs: Vec<(usize, SimConfig)> = configs.iter().cloned().enumerate().skip(t).step_by(threads).collect();
+            let data = data.clone();
+            let setup = setup.clone();
+            // Errors are passed as text, they don't need to be sent between threads
+            thread::spawn(move || -> Result<Vec<(usize, Batch)>, String> {
+                let mut batches = Vec::with_capacity(configs.len());
+                for (i, config) in configs {
+                    let batch = Batch::play(config, &data, &setup, games, seed).map_err(|e| e.to_string())?;
+                    batches.push((i, batch));
+                }
+                Ok(batches)
+            })
+        }).collect();
+        let mut batches = Vec::with_capacity(configs.len());
+        for handle in handles {
+            let done = handle.join().map_err(|_| GameError::UnknownError(String::from("A sweep thread panicked")))?;
+            batches.extend(done.map_err(GameError::UnknownError)?);
+        }
+        batches.sort_by_key(|&(i, _)| i);
+        let batches: Vec<Batch> = batches.into_iter().map(|(_, batch)| batch).collect();
+        let mut kinds = Vec::new();
+        for &(kind, _, _) in batches.iter().flat_map(|batch| &batch.kinds) {
+            if !kinds.contains(&kind) {
+                kinds.push(kind);
+            }
+        }
+        Ok(Sweep{
+            batches: batches,
+            kinds: kinds,
+        })
+    }
+
+    /// Returns one line per config and unit kind, with a header
+    pub fn to_csv(&self) -> String {
+        let mut csv = String::from("movement_speed,damage_scale,map_scale,games,draws,game_length,kind,win_rate\n");
+        for batch in &self.batches {
+            for &kind in &self.kinds {
+                csv.push_str(&format!("{},{},{},{},{},{},{:?},{}\n",
+                    batch.config.movement_speed, batch.config.damage_scale, batch.config.map_scale,
+                    batch.games, batch.draws,
+                    batch.game_length().map(|seconds| format!("{:.1}", seconds)).unwrap_or_default(),
+                    kind,
+                    batch.win_rate(kind).map(|rate| format!("{:.3}", rate)).unwrap_or_default()));
+            }
+        }
+        csv
+    }
+
+    /// Returns a table with a row per config and a column per unit kind, holding the kind's win rate
+    pub fn to_markdown(&self) -> String {
+        let mut table = String::from("Share of each unit kind built by the winning team, per config\n\n");
+        table.push_str("| Movement speed | Damage scale | Map scale | Draws | Game length |");
+        for kind in &self.kinds {
+            table.push_str(&format!(" {:?} |", kind));
+        }
+        table.push_str("\n| --- | --- | --- | --- | --- |");
+        for _ in &self.kinds {
+            table.push_str(" --- |");
+        }
+        table.push('\n');
+        for batch in &self.batches {
+            let length = batch.game_length().map(|seconds| format!("{:.0}s", seconds)).unwrap_or_else(|| String::from("-"));
+            table.push_str(&format!("| {} | {} | {} | {}/{} | {} |",
+                batch.config.movement_speed, batch.config.damage_scale, batch.config.map_scale,
+                batch.draws, batch.games, length));
+            for &kind in &self.kinds {
+                match batch.win_rate(kind) {
+                    Some(rate) => table.push_str(&format!(" {:.0}% |", rate*100.0)),
+                    None => table.push_str(" - |"),
+                }
+            }
+            table.push('\n');
+        }
+        table
+    }
+
+    /// Writes the sweep to the passed paths, as CSV and Markdown
+    pub fn write(&self, csv: &str, markdown: &str) -> GameResult<()> {
+        File::create(csv)?.write_all(self.to_csv().as_bytes())?;
+        File::create(markdown)?.write_all(self.to_markdown().as_bytes())?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn batch(games: u32, draws: u32, seconds: f32) -> Batch {
+        Batch{
+            config: SimConfig{movement_speed: 0.01, damage_scale: 0.02, map_scale: 500.0},
+            games: games,
+            draws: draws,
+            seconds: seconds,
+            kinds: vec![(UnitKind::Ling, 3, 4), (UnitKind::Marine, 1, 4)],
+        }
+    }
+
+    #[test]
+    fn grid_around_a_config_has_every_combination() {
+        let config = SimConfig::default();
+        let configs = Grid::around(config).configs();
+        assert_eq!(configs.len(), 27);
+        assert!(configs.contains(&config));
+        assert!(configs.contains(&SimConfig{
+            movement_speed: config.movement_speed*0.5,
+            damage_scale: config.damage_scale*2.0,
+            map_scale: config.map_scale,
+        }));
+    }
+
+    #[test]
+    fn win_rate_is_the_share_built_by_winners() {
+        let batch = batch(4, 1, 90.0);
+        assert_eq!(batch.win_rate(UnitKind::Ling), Some(0.75));
+        assert_eq!(batch.win_rate(UnitKind::Marine), Some(0.25));
+        assert_eq!(batch.win_rate(UnitKind::Hydra), None);
+    }
+
+    #[test]
+    fn game_length_only_counts_decided_games() {
+        assert_eq!(batch(4, 1, 90.0).game_length(), Some(30.0));
+        assert_eq!(batch(3, 3, 0.0).game_length(), None);
+    }
+
+    #[test]
+    fn reports_have_a_column_per_value() {
+        // Only decided games count towards the win rates
+        let mut drawn = batch(2, 2, 0.0);
+        drawn.kinds.clear();
+        let sweep = Sweep{
+            batches: vec![batch(4, 1, 90.0), drawn],
+            kinds: vec![UnitKind::Ling, UnitKind::Marine],
+        };
+        let csv = sweep.to_csv();
+        let lines: Vec<&str> = csv.lines().collect();
+        assert_eq!(lines, vec![
+            "movement_speed,damage_scale,map_scale,games,draws,game_length,kind,win_rate",
+            "0.01,0.02,500,4,1,30.0,Ling,0.750",
+            "0.01,0.02,500,4,1,30.0,Marine,0.250",
+            "0.01,0.02,500,2,2,,Ling,",
+            "0.01,0.02,500,2,2,,Marine,",
+        ]);
+        let markdown = sweep.to_markdown();
+        let rows: Vec<&str> = markdown.lines().skip(2).collect();
+        assert_eq!(rows, vec![
+            "| Movement speed | Damage scale | Map scale | Draws | Game length | Ling | Marine |",
+            "| --- | --- | --- | --- | --- | --- | --- |",
+            "| 0.01 | 0.02 | 500 | 1/4 | 30s | 75% | 25% |",
+            "| 0.01 | 0.02 | 500 | 2/2 | - | - | - |",
+        ]);
+    }
+}
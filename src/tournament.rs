@@ -0,0 +1,319 @@
+use ggez::{GameResult, GameError};
+use serde_json;
+
+use std::fs::File;
+use std::path::Path;
+
+use player::Controltype;
+use data::GameData;
+use setup::Setup;
+use simulation::Simulation;
+use constants::GAME_TIMEOUT;
+
+
+/// Rating of entrants that haven't played yet
+const INITIAL_RATING: f32 = 1500.0;
+
+/// How much a single game can change a rating
+const K_FACTOR: f32 = 24.0;
+
+/// A controller taking part in a tournament, playing a fixed faction
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Entrant{
+    pub name: String,
+    #[serde(default = "zerg")]
+    pub faction: String,
+    pub controller: Controltype,
+}
+
+fn zerg() -> String {
+    String::from("zerg")
+}
+
+impl Entrant {
+    /// Reads a list of entrants from the json file at the passed path
+    pub fn read(path: &str) -> GameResult<Vec<Entrant>> {
+        let file = File::open(path)?;
+        serde_json::from_reader(file)
+            .map_err(|e| GameError::ResourceLoadError(format!("invalid entrants: {}", e)))
+    }
+}
+
+/// Who plays whom in a tournament
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Schedule{
+    /// Every entrant plays every other once
+    RoundRobin,
+    /// The passed number of rounds, each pairing entrants with similar scores that haven't played each other yet
+    Swiss(u32),
+}
+
+/// The rating of a single entrant, together with its record
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Rating{
+    pub name: String,
+    pub rating: f32,
+    pub wins: u32,
+    pub draws: u32,
+    pub losses: u32,
+}
+
+/// Elo ratings of all entrants that have ever played, kept across tournaments in a json file
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct Ratings{
+    pub ratings: Vec<Rating>,
+}
+
+impl Ratings {
+    /// Reads the ratings from the json file at the passed path, starting from scratch if there is none yet
+    pub fn read(path: &str) -> GameResult<Ratings> {
+        if !Path::new(path).exists() {
+            return Ok(Ratings::default());
+        }
+        let file = File::open(path)?;
+        serde_json::from_reader(file)
+            .map_err(|e| GameError::ResourceLoadError(format!("invalid ratings: {}", e)))
+    }
+
+    /// Writes the ratings to the json file at the passed path
+    pub fn write(&self, path: &str) -> GameResult<()> {
+        let file = File::create(path)?;
+        serde_json::to_writer_pretty(file, self)
+            .map_err(|e| GameError::UnknownError(format!("could not write ratings: {}", e)))
+    }
+
+    /// Returns the rating of the entrant with the passed name
+    pub fn rating(&self, name: &str) -> f32 {
+        self.ratings.iter().find(|rating| rating.name == name).map(|rating| rating.rating).unwrap_or(INITIAL_RATING)
+    }
+
+    /// Returns the index of the entrant with the passed name, adding it if it hasn't played before
+    fn index(&mut self, name: &str) -> usize {
+        match self.ratings.iter().position(|rating| rating.name == name) {
+            Some(index) => index,
+            None => {
+                self.ratings.push(Rating{
+                    name: name.to_string(),
+                    rating: INITIAL_RATING,
+                    wins: 0,
+                    draws: 0,
+                    losses: 0,
+                });
+                self.ratings.len() - 1
+            }
+        }
+    }
+
+    /// Updates the ratings and records of both entrants after a game,
+    /// `score` being 1.0 if the first won, 0.5 on a draw and 0.0 if the second won
+    pub fn record(&mut self, first: &str, second: &str, score: f32) {
+        let (a, b) = (self.index(first), self.index(second));
+        let expected = 1.0/(1.0 + 10f32.powf((self.ratings[b].rating - self.ratings[a].rating)/400.0));
+        let change = K_FACTOR*(score - expected);
+        self.ratings[a].rating += change;
+        self.ratings[b].rating -= change;
+        if score > 0.5 {
+            self.ratings[a].wins += 1;
+            self.ratings[b].losses += 1;
+        } else if score < 0.5 {
+            self.ratings[a].losses += 1;
+            self.ratings[b].wins += 1;
+        } else {
+            self.ratings[a].draws += 1;
+            self.ratings[b].draws += 1;
+        }
+    }
+
+    /// Returns a table of all entrants, best rated first
+    pub fn leaderboard(&self) -> String {
+        let mut ratings: Vec<&Rating> = self.ratings.iter().collect();
+        ratings.sort_by(|a, b| b.rating.partial_cmp(&a.rating).unwrap());
+        let mut table = String::from("| # | Entrant | Rating | Wins | Draws | Losses |\n| --- | --- | --- | --- | --- | --- |\n");
+        for (i, rating) in ratings.iter().enumerate() {
+            table.push_str(&format!("| {} | {} | {:.0} | {} | {} | {} |\n",
+                i + 1, rating.name, rating.rating, rating.wins, rating.draws, rating.losses));
+        }
+        table
+    }
+}
+
+/// Pairs the entrants in `order`, best first, each with the next entrant it hasn't played yet, or rather plays again than sits out.
+/// With an odd number of entrants, the lowest ranked entrant that hasn't had a bye yet sits out the round.
+/// Returns the pairings and who sits out
+fn pair(mut order: Vec<usize>, played: &[(usize, usize)], byes: &[usize]) -> (Vec<(usize, usize)>, Option<usize>) {
+    let bye = if order.len() % 2 == 1 {
+        let index = order.iter().rposition(|a| !byes.contains(a)).unwrap_or(order.len() - 1);
+        Some(order.remove(index))
+    } else {
+        None
+    };
+    let mut pairings = Vec::with_capacity(order.len()/2);
+    while !order.is_empty() {
+        let a = order.remove(0);
+        let fresh = order.iter().position(|&b| !played.contains(&(a, b)) && !played.contains(&(b, a))).unwrap_or(0);
+        pairings.push((a, order.remove(fresh)));
+    }
+    (pairings, bye)
+}
+
+/// AI-vs-AI games between registered controllers. Every pairing plays each map with each seed from both sides
+#[derive(Debug, Clone)]
+pub struct Tournament{
+    pub entrants: Vec<Entrant>,
+    /// Names of the two player modes to play on, as accepted by `Setup::from_name`
+    pub maps: Vec<String>,
+    /// Number of seeds each pairing plays per map and side
+    pub seeds: u32,
+    /// Seed of the first game of each pairing
+    pub seed: u64,
+}
+
+impl Tournament {
+    /// Plays the passed schedule, updating the ratings after every game.
+    /// Returns the tournament points of each entrant: 1 per won game, 0.5 per draw
+    pub fn run(&self, data: &GameData, schedule: Schedule, ratings: &mut Ratings) -> GameResult<Vec<f32>> {
+        let mut points = vec!(0.0; self.entrants.len());
+        match schedule {
+            Schedule::RoundRobin => {
+                for a in 0..self.entrants.len() {
+                    for b in a + 1..self.entrants.len() {
+                        self.play_pairing(data, a, b, ratings, &mut points)?;
+                    }
+                }
+            }
+            Schedule::Swiss(rounds) => {
+                let mut played: Vec<(usize, usize)> = Vec::new();
+                let mut byes: Vec<usize> = Vec::new();
+                for round in 0..rounds {
+                    println!("Round {}", round + 1);
+                    // Entrants with more points, and on a tie the better rated ones, are paired first
+                    let mut order: Vec<usize> = (0..self.entrants.len()).collect();
+                    order.sort_by(|&a, &b| (points[b], ratings.rating(&self.entrants[b].name))
+                        .partial_cmp(&(points[a], ratings.rating(&self.entrants[a].name))).unwrap());
+                    let (pairings, bye) = pair(order, &played, &byes);
+                    if let Some(a) = bye {
+                        // A bye is worth as much as winning a whole pairing
+                        println!("{} has a bye", self.entrants[a].name);
+                        points[a] += self.games_per_pairing() as f32;
+                        byes.push(a);
+                    }
+                    for (a, b) in pairings {
+                        played.push((a, b));
+                        self.play_pairing(data, a, b, ratings, &mut points)?;
+                    }
+                }
+            }
+        }
+        Ok(points)
+    }
+
+    /// Returns the number of games each pairing plays
+    fn games_per_pairing(&self) -> u32 {
+        self.maps.len() as u32*self.seeds*2
+    }
+
+    /// Plays all games between the entrants at the passed indices, on every map with every seed from both sides
+    fn play_pairing(&self, data: &GameData, a: usize, b: usize, ratings: &mut Ratings, points: &mut [f32]) -> GameResult<()> {
+        let (first, second) = (&self.entrants[a], &self.entrants[b]);
+        let mut score = 0.0;
+        for map in &self.maps {
+            for n in 0..self.seeds {
+                let seed = self.seed.wrapping_add(n as u64);
+                for &swapped in &[false, true] {
+                    let game = if swapped {
+                        self.play(data, map, second, first, seed)?.map(|first_won| !first_won)
+                    } else {
+                        self.play(data, map, first, second, seed)?
+                    };
+                    let game = match game {
+                        Some(true) => 1.0,
+                        Some(false) => 0.0,
+                        None => 0.5,
+                    };
+                    ratings.record(&first.name, &second.name, game);
+                    score += game;
+                }
+            }
+        }
+        let games = self.games_per_pairing() as f32;
+        println!("{} vs {}: {} - {}", first.name, second.name, score, games - score);
+        points[a] += score;
+        points[b] += games - score;
+        Ok(())
+    }
+
+    /// Plays a single game on the passed map, `left` playing the first slot and `right` the second.
+    /// Returns whether `left` won, or `None` on a draw
+    fn play(&self, data: &GameData, map: &str, left: &Entrant, right: &Entrant, seed: u64) -> GameResult<Option<bool>> {
+        let mut setup = match Setup::from_name(map) {
+            Some(ref setup) if setup.slots.len() == 2 => setup.clone(),
+            _ => return Err(GameError::UnknownError(format!("{} is not a map for two players", map))),
+        };
+        for (slot, entrant) in setup.slots.iter_mut().zip(&[left, right]) {
+            slot.ai = true;
+            slot.faction = entrant.faction.clone();
+            slot.controller = Some(entrant.controller.clone());
+        }
+        let mut sim = Simulation::new(&setup, data, seed)?;
+        sim.run(GAME_TIMEOUT)?;
+        Ok(match sim.winner {
+            Some(team) if sim.over => Some(team == sim.players[0].team),
+            _ => None,
+        })
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn rating_changes_are_symmetric() {
+        let mut ratings = Ratings::default();
+        ratings.record("a", "b", 1.0);
+        assert_eq!(ratings.rating("a") - INITIAL_RATING, INITIAL_RATING - ratings.rating("b"));
+        assert_eq!(ratings.rating("a"), INITIAL_RATING + K_FACTOR/2.0);
+        ratings.record("b", "a", 1.0);
+        assert_eq!(ratings.rating("a") + ratings.rating("b"), 2.0*INITIAL_RATING);
+    }
+
+    #[test]
+    fn draw_between_equal_ratings_changes_nothing() {
+        let mut ratings = Ratings::default();
+        ratings.record("a", "b", 0.5);
+        assert_eq!(ratings.rating("a"), INITIAL_RATING);
+        assert_eq!(ratings.rating("b"), INITIAL_RATING);
+        assert_eq!((ratings.ratings[0].draws, ratings.ratings[1].draws), (1, 1));
+    }
+
+    #[test]
+    fn upsets_move_ratings_more_than_expected_wins() {
+        let mut ratings = Ratings::default();
+        ratings.record("strong", "weak", 1.0);
+        let before = ratings.rating("strong");
+        let mut upset = ratings.clone();
+        upset.record("weak", "strong", 1.0);
+        ratings.record("strong", "weak", 1.0);
+        assert!(before - upset.rating("strong") > ratings.rating("strong") - before);
+    }
+
+    #[test]
+    fn byes_go_to_a_different_entrant_every_round() {
+        let mut byes = Vec::new();
+        for _ in 0..3 {
+            let (pairings, bye) = pair(vec!(0, 1, 2), &[], &byes);
+            assert_eq!(pairings.len(), 1);
+            byes.push(bye.unwrap());
+        }
+        byes.sort();
+        assert_eq!(byes, vec!(0, 1, 2));
+    }
+
+    #[test]
+    fn rematches_are_avoided() {
+        let (pairings, bye) = pair(vec!(0, 1, 2, 3), &[(0, 1)], &[]);
+        assert_eq!(bye, None);
+        assert_eq!(pairings, vec!((0, 2), (1, 3)));
+    }
+}
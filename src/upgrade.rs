@@ -0,0 +1,134 @@
+use gameobject::{CharStats, UnitKind};
+
+
+/// A researchable upgrade, as defined in the data file
+/// Each level of the upgrade adds its bonuses once to the stats of every affected unit
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Upgrade{
+    pub name: String,
+    pub cost: u32,
+    pub max_level: u32,
+    pub applies_to: Vec<UnitKind>,
+    #[serde(default)]
+    pub damage: f32,
+    #[serde(default)]
+    pub armor: f32,
+    #[serde(default)]
+    pub speed: f32,
+    #[serde(default)]
+    pub range: f32,
+}
+
+impl Upgrade {
+    /// Returns true if the upgrade affects units of the specified kind
+    pub fn applies_to(&self, kind: UnitKind) -> bool {
+        self.applies_to.contains(&kind)
+    }
+
+    /// Returns the mineral cost of researching the level following `level`
+    /// Every level costs as much as the previous one plus the base cost
+    pub fn cost_at(&self, level: u32) -> u32 {
+        self.cost * (level + 1)
+    }
+
+    /// Adds the bonuses of one upgrade level to the passed stats
+    pub fn apply(&self, stats: &mut CharStats){
+        stats.damage += self.damage;
+        stats.armor += self.armor;
+        stats.speed += self.speed;
+        stats.range += self.range;
+    }
+}
+
+
+/// Tracks a player's progress in researching a single `Upgrade`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Research{
+    pub upgrade: Upgrade,
+    pub level: u32,
+}
+
+impl Research {
+    /// Returns a new, unresearched `Research` of the passed upgrade
+    pub fn new(upgrade: Upgrade) -> Research {
+        Research{
+            upgrade,
+            level: 0,
+        }
+    }
+
+    /// Returns true if the upgrade can't be researched any further
+    pub fn is_maxed(&self) -> bool {
+        self.level >= self.upgrade.max_level
+    }
+
+    /// Returns the mineral cost of the next level
+    pub fn next_cost(&self) -> u32 {
+        self.upgrade.cost_at(self.level)
+    }
+
+    /// Applies all researched levels to the stats of a unit of the specified kind
+    pub fn apply(&self, kind: UnitKind, stats: &mut CharStats){
+        if self.upgrade.applies_to(kind) {
+            for _ in 0..self.level {
+                self.upgrade.apply(stats);
+            }
+        }
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json;
+
+    fn melee() -> Upgrade {
+        Upgrade{
+            name: String::from("Melee Attacks"),
+            cost: 100,
+            max_level: 3,
+            applies_to: vec!(UnitKind::Ling, UnitKind::Bane),
+            damage: 1.0,
+            armor: 0.0,
+            speed: 0.0,
+            range: 0.0,
+        }
+    }
+
+    fn stats() -> CharStats {
+        serde_json::from_str(r#"{"cost": 50, "hp": 35.0, "damage": 5.0, "speed": 1.0, "range": 0.1, "targets": 1}"#).unwrap()
+    }
+
+    #[test]
+    fn cost_rises_by_the_base_cost_per_level() {
+        let mut research = Research::new(melee());
+        assert_eq!(research.next_cost(), 100);
+        research.level = 1;
+        assert_eq!(research.next_cost(), 200);
+        research.level = 2;
+        assert_eq!(research.next_cost(), 300);
+        assert!(!research.is_maxed());
+        research.level = 3;
+        assert!(research.is_maxed());
+    }
+
+    #[test]
+    fn every_level_applies_its_bonus_once() {
+        let mut research = Research::new(melee());
+        research.level = 2;
+        let mut ling = stats();
+        research.apply(UnitKind::Ling, &mut ling);
+        assert_eq!(ling.damage, 7.0);
+        assert_eq!(ling.hp, 35.0);
+    }
+
+    #[test]
+    fn unaffected_kinds_keep_their_stats() {
+        let mut research = Research::new(melee());
+        research.level = 3;
+        let mut hydra = stats();
+        research.apply(UnitKind::Hydra, &mut hydra);
+        assert_eq!(hydra.damage, 5.0);
+    }
+}